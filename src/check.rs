@@ -0,0 +1,130 @@
+// Sanity checks of the libraries found on the system, by compiling and linking small C programs
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::Library;
+
+pub(crate) struct Checker {
+    cc: String,
+    args: Vec<String>,
+    dir: PathBuf,
+}
+
+impl Checker {
+    /// `cc` is the compiler program, `args` the flags passed before any other argument.
+    pub(crate) fn new(cc: String, args: Vec<String>, out_dir: &Path) -> Self {
+        Self {
+            cc,
+            args,
+            dir: out_dir.join("system-deps-check"),
+        }
+    }
+
+    /// Check that `headers` can be included using the include paths and defines of `lib`.
+    pub(crate) fn check_headers(
+        &self,
+        name: &str,
+        lib: &Library,
+        headers: &[String],
+    ) -> Result<(), String> {
+        let mut source = String::new();
+        for h in headers {
+            writeln!(source, "#include <{h}>").unwrap();
+        }
+        source.push_str("\nint main(void) { return 0; }\n");
+
+        let src = self.write_source(&format!("{name}-headers.c"), &source)?;
+        let mut cmd = self.command();
+        cmd.arg("-c")
+            .arg(&src)
+            .arg("-o")
+            .arg(self.dir.join(format!("{name}-headers.o")));
+        for path in lib.include_paths.iter() {
+            cmd.arg(format!("-I{}", path.display()));
+        }
+        for (k, v) in lib.defines.iter() {
+            match v {
+                Some(v) => cmd.arg(format!("-D{k}={v}")),
+                None => cmd.arg(format!("-D{k}")),
+            };
+        }
+
+        run(cmd)
+    }
+
+    /// Check that `symbols` can be resolved when linking with `lib`.
+    pub(crate) fn check_symbols(
+        &self,
+        name: &str,
+        lib: &Library,
+        symbols: &[String],
+    ) -> Result<(), String> {
+        // Declare the symbols with a dummy prototype, as autoconf does, so no header is needed
+        let mut source = String::new();
+        for s in symbols {
+            writeln!(source, "char {s}(void);").unwrap();
+        }
+        source.push_str("\nint main(void) {\n");
+        for s in symbols {
+            writeln!(source, "    {s}();").unwrap();
+        }
+        source.push_str("    return 0;\n}\n");
+
+        let src = self.write_source(&format!("{name}-symbols.c"), &source)?;
+        let mut cmd = self.command();
+        cmd.arg(&src)
+            .arg("-o")
+            .arg(self.dir.join(format!("{name}-symbols")));
+        for path in lib.link_paths.iter() {
+            cmd.arg(format!("-L{}", path.display()));
+        }
+        for path in lib.framework_paths.iter() {
+            cmd.arg(format!("-F{}", path.display()));
+        }
+        for l in lib.libs.iter() {
+            cmd.arg(format!("-l{}", l.name));
+        }
+        for f in lib.frameworks.iter() {
+            cmd.arg("-framework").arg(f);
+        }
+        for args in lib.ld_args.iter() {
+            cmd.arg(format!("-Wl,{}", args.join(",")));
+        }
+
+        run(cmd)
+    }
+
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.cc);
+        cmd.args(&self.args);
+        cmd
+    }
+
+    fn write_source(&self, file: &str, source: &str) -> Result<PathBuf, String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("failed to create {}: {e}", self.dir.display()))?;
+        let path = self.dir.join(file);
+        fs::write(&path, source).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        Ok(path)
+    }
+}
+
+fn run(mut cmd: Command) -> Result<(), String> {
+    let output = cmd
+        .output()
+        .map_err(|e| format!("failed to run {cmd:?}: {e}"))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{cmd:?} failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ))
+    }
+}
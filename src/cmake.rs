@@ -0,0 +1,413 @@
+// Probe libraries shipping CMake package config files, using a generated CMake project
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    metadata::CMakePackage,
+    pc::{PcLibrary, Resolver},
+};
+
+// Write the version and the usage requirements of the imported targets to `SYSTEM_DEPS_OUTPUT`
+const PROJECT: &str = r#"cmake_minimum_required(VERSION 3.21)
+project(system_deps_probe LANGUAGES C)
+
+find_package(@PACKAGE@ REQUIRED@COMPONENTS@)
+
+file(WRITE "${SYSTEM_DEPS_OUTPUT}" "version=${@PACKAGE@_VERSION}\n")
+get_property(targets DIRECTORY PROPERTY IMPORTED_TARGETS)
+foreach(target IN LISTS targets)
+  get_target_property(type ${target} TYPE)
+  if(NOT type MATCHES "_LIBRARY$")
+    continue()
+  endif()
+
+  set(location "")
+  if(NOT type STREQUAL "INTERFACE_LIBRARY")
+    set(properties IMPORTED_IMPLIB_RELEASE IMPORTED_LOCATION_RELEASE)
+    get_target_property(configs ${target} IMPORTED_CONFIGURATIONS)
+    if(configs)
+      foreach(config IN LISTS configs)
+        list(APPEND properties IMPORTED_IMPLIB_${config} IMPORTED_LOCATION_${config})
+      endforeach()
+    endif()
+    list(APPEND properties IMPORTED_IMPLIB IMPORTED_LOCATION)
+    foreach(property IN LISTS properties)
+      get_target_property(location ${target} ${property})
+      if(location)
+        break()
+      endif()
+    endforeach()
+  endif()
+
+  get_target_property(include ${target} INTERFACE_INCLUDE_DIRECTORIES)
+  get_target_property(defines ${target} INTERFACE_COMPILE_DEFINITIONS)
+  get_target_property(libs ${target} INTERFACE_LINK_LIBRARIES)
+  file(APPEND "${SYSTEM_DEPS_OUTPUT}" "target=${target}\nlocation=${location}\ninclude=${include}\ndefines=${defines}\nlibs=${libs}\n")
+endforeach()
+"#;
+
+pub(crate) struct CMakeProbe {
+    cmake: String,
+    dir: PathBuf,
+    toolchain_file: Option<String>,
+}
+
+impl CMakeProbe {
+    pub(crate) fn new(cmake: String, out_dir: &Path, name: &str) -> Self {
+        Self {
+            cmake,
+            dir: out_dir.join("system-deps-cmake").join(name),
+            toolchain_file: None,
+        }
+    }
+
+    /// Configure the project for the target described in `toolchain_file`.
+    pub(crate) fn toolchain_file(&mut self, toolchain_file: String) -> &mut Self {
+        self.toolchain_file = Some(toolchain_file);
+        self
+    }
+
+    /// Find `package` and return the flags needed to use all its imported targets,
+    /// and its version, as well as the libraries only available as static archives.
+    pub(crate) fn probe(
+        &self,
+        package: &CMakePackage,
+        resolver: &Resolver,
+    ) -> Result<(PcLibrary, Vec<String>), String> {
+        let components = if package.components.is_empty() {
+            String::new()
+        } else {
+            format!(" COMPONENTS {}", package.components.join(" "))
+        };
+        let project = PROJECT
+            .replace("@PACKAGE@", &package.package)
+            .replace("@COMPONENTS@", &components);
+
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("failed to create {}: {e}", self.dir.display()))?;
+        let lists = self.dir.join("CMakeLists.txt");
+        fs::write(&lists, project)
+            .map_err(|e| format!("failed to write {}: {e}", lists.display()))?;
+
+        let output = self.dir.join("output.txt");
+        let mut cmd = Command::new(&self.cmake);
+        cmd.arg("-S")
+            .arg(&self.dir)
+            .arg("-B")
+            .arg(self.dir.join("build"))
+            .arg(format!("-DSYSTEM_DEPS_OUTPUT={}", output.display()));
+        if let Some(toolchain_file) = &self.toolchain_file {
+            cmd.arg(format!("-DCMAKE_TOOLCHAIN_FILE={toolchain_file}"));
+        }
+        let result = cmd
+            .output()
+            .map_err(|e| format!("failed to run {cmd:?}: {e}"))?;
+        if !result.status.success() {
+            return Err(format!(
+                "{cmd:?} failed:\n{}",
+                String::from_utf8_lossy(&result.stderr).trim_end()
+            ));
+        }
+
+        let output = fs::read_to_string(&output)
+            .map_err(|e| format!("failed to read {}: {e}", output.display()))?;
+        let (version, targets) = parse_output(&output);
+
+        let mut lib = resolver.parse_words(&flags(&targets));
+        lib.version = version;
+        Ok((lib, archives(&targets)))
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Target {
+    name: String,
+    location: Option<String>,
+    include: Vec<String>,
+    defines: Vec<String>,
+    libs: Vec<String>,
+}
+
+fn parse_output(output: &str) -> (String, Vec<Target>) {
+    // Properties which are not set are reported as `<var>-NOTFOUND`
+    fn list(value: &str) -> Vec<String> {
+        if value.ends_with("-NOTFOUND") {
+            return Vec::new();
+        }
+        value
+            .split(';')
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    let mut version = String::new();
+    let mut targets: Vec<Target> = Vec::new();
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match (key, targets.last_mut()) {
+            ("version", _) => version = value.to_string(),
+            ("target", _) => targets.push(Target {
+                name: value.to_string(),
+                ..Default::default()
+            }),
+            ("location", Some(t)) => t.location = list(value).into_iter().next(),
+            ("include", Some(t)) => t.include = list(value),
+            ("defines", Some(t)) => t.defines = list(value),
+            ("libs", Some(t)) => t.libs = list(value),
+            _ => {}
+        }
+    }
+
+    (version, targets)
+}
+
+// Convert the usage requirements of the targets into compiler and linker flags.
+// Targets are ordered so the ones depending on others come first, as when linking.
+fn flags(targets: &[Target]) -> Vec<String> {
+    fn visit<'a>(
+        target: &'a Target,
+        targets: &HashMap<&str, &'a Target>,
+        visited: &mut HashSet<&'a str>,
+        order: &mut Vec<&'a Target>,
+    ) {
+        if !visited.insert(&target.name) {
+            return;
+        }
+        for lib in target.libs.iter() {
+            if let Some(dep) = targets.get(link_item(lib)) {
+                visit(dep, targets, visited, order);
+            }
+        }
+        order.push(target);
+    }
+
+    let by_name = targets.iter().map(|t| (t.name.as_str(), t)).collect();
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    for target in targets {
+        visit(target, &by_name, &mut visited, &mut order);
+    }
+
+    let mut cflags = Vec::new();
+    let mut libs = Vec::new();
+    for target in order.into_iter().rev() {
+        for path in target.include.iter().filter(|p| !p.starts_with("$<")) {
+            let flag = format!("-I{path}");
+            if !cflags.contains(&flag) {
+                cflags.push(flag);
+            }
+        }
+        for define in target.defines.iter().filter(|d| !d.starts_with("$<")) {
+            let flag = format!("-D{define}");
+            if !cflags.contains(&flag) {
+                cflags.push(flag);
+            }
+        }
+        if let Some(location) = &target.location {
+            libs.extend(file_flags(location));
+        }
+        for lib in target.libs.iter() {
+            let item = link_item(lib);
+            if item.starts_with("$<") || by_name.contains_key(item) || item.contains("::") {
+                // Generator expressions can't be evaluated here and targets
+                // are handled on their own.
+                continue;
+            } else if item.starts_with("-l") || item.starts_with("-Wl,") {
+                libs.push(item.to_string());
+            } else if let Some(framework) = item.strip_prefix("-framework ") {
+                libs.push("-framework".to_string());
+                libs.push(framework.to_string());
+            } else if item.starts_with('-') {
+                // Other flags, such as `-pthread`, are not supported by cargo
+                continue;
+            } else if Path::new(item).is_absolute() {
+                libs.extend(file_flags(item));
+            } else {
+                libs.push(format!("-l{item}"));
+            }
+        }
+    }
+
+    cflags.extend(libs);
+    cflags
+}
+
+// Remove the `$<LINK_ONLY:...>` wrapper CMake adds to private dependencies of static libraries
+fn link_item(lib: &str) -> &str {
+    lib.strip_prefix("$<LINK_ONLY:")
+        .and_then(|l| l.strip_suffix('>'))
+        .unwrap_or(lib)
+}
+
+// The libraries of the targets which are static archives, and so can only be linked statically
+fn archives(targets: &[Target]) -> Vec<String> {
+    let mut archives = Vec::new();
+    for target in targets {
+        let files = target.libs.iter().map(|l| link_item(l));
+        for file in target.location.as_deref().into_iter().chain(files) {
+            let path = Path::new(file);
+            if !path.is_absolute() || !file.ends_with(".a") {
+                continue;
+            }
+            if let Some(file) = path.file_name().and_then(|f| f.to_str()) {
+                let name = lib_name(file).to_string();
+                if !archives.contains(&name) {
+                    archives.push(name);
+                }
+            }
+        }
+    }
+    archives
+}
+
+// Flags to link with the library at `location`
+fn file_flags(location: &str) -> Vec<String> {
+    let path = Path::new(location);
+    let (Some(dir), Some(file)) = (path.parent(), path.file_name()) else {
+        return Vec::new();
+    };
+    let file = file.to_string_lossy();
+
+    // Frameworks are located at `<dir>/Foo.framework/Foo`
+    if let Some(framework) = dir
+        .file_name()
+        .and_then(|f| f.to_str())
+        .and_then(|f| f.strip_suffix(".framework"))
+    {
+        let dir = dir.parent().unwrap_or(dir);
+        return vec![
+            format!("-F{}", dir.display()),
+            "-framework".to_string(),
+            framework.to_string(),
+        ];
+    }
+
+    vec![
+        format!("-L{}", dir.display()),
+        format!("-l{}", lib_name(&file)),
+    ]
+}
+
+// The name of the library file `file`, as passed to `-l`
+fn lib_name(file: &str) -> &str {
+    // MSVC libraries have no `lib` prefix
+    if let Some(name) = file.strip_suffix(".lib") {
+        return name;
+    }
+    let name = strip_so_suffix(file)
+        .unwrap_or_else(|| file.rsplit_once('.').map_or(file, |(name, _)| name));
+    name.strip_prefix("lib").unwrap_or(name)
+}
+
+// Shared objects may have a version after their suffix, such as `libfoo.so.1.2`
+fn strip_so_suffix(file: &str) -> Option<&str> {
+    if let Some(name) = file.strip_suffix(".so") {
+        return Some(name);
+    }
+    let (name, version) = file.rsplit_once(".so.")?;
+    let is_version = version
+        .split('.')
+        .all(|v| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit()));
+    is_version.then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output() {
+        let output = "version=1.2.3
+target=Foo::core
+location=/opt/foo/lib/libfoo-core.so.1.2
+include=/opt/foo/include
+defines=FOO_SHARED;FOO_LEVEL=2
+libs=m;$<LINK_ONLY:Threads::Threads>
+target=Threads::Threads
+location=
+include=include-NOTFOUND
+defines=defines-NOTFOUND
+libs=-pthread
+target=Foo::gui
+location=/opt/foo/lib/libfoo-gui.a
+include=/opt/foo/include;/opt/foo/include/gui
+defines=defines-NOTFOUND
+libs=Foo::core;-Wl,--as-needed;/opt/bar/lib/libbar.dylib
+";
+        let (version, targets) = parse_output(output);
+        assert_eq!(version, "1.2.3");
+        assert_eq!(targets.len(), 3);
+        assert_eq!(
+            targets[0],
+            Target {
+                name: "Foo::core".into(),
+                location: Some("/opt/foo/lib/libfoo-core.so.1.2".into()),
+                include: vec!["/opt/foo/include".into()],
+                defines: vec!["FOO_SHARED".into(), "FOO_LEVEL=2".into()],
+                libs: vec!["m".into(), "$<LINK_ONLY:Threads::Threads>".into()],
+            }
+        );
+        assert_eq!(targets[1].location, None);
+        assert!(targets[1].include.is_empty());
+
+        assert_eq!(
+            flags(&targets),
+            vec![
+                "-I/opt/foo/include",
+                "-I/opt/foo/include/gui",
+                "-DFOO_SHARED",
+                "-DFOO_LEVEL=2",
+                "-L/opt/foo/lib",
+                "-lfoo-gui",
+                "-Wl,--as-needed",
+                "-L/opt/bar/lib",
+                "-lbar",
+                "-L/opt/foo/lib",
+                "-lfoo-core",
+                "-lm",
+            ]
+        );
+    }
+
+    #[test]
+    fn framework() {
+        assert_eq!(
+            file_flags("/Library/Frameworks/Foo.framework/Foo"),
+            vec!["-F/Library/Frameworks", "-framework", "Foo"]
+        );
+        assert_eq!(
+            file_flags("C:/foo/lib/foo.lib"),
+            vec!["-LC:/foo/lib", "-lfoo"]
+        );
+    }
+
+    #[test]
+    fn lib_names() {
+        assert_eq!(lib_name("libfoo.so"), "foo");
+        assert_eq!(lib_name("libfoo.so.1.2"), "foo");
+        assert_eq!(lib_name("libfoo.socket.a"), "foo.socket");
+        assert_eq!(lib_name("libfoo.sock.so"), "foo.sock");
+        assert_eq!(lib_name("libfoo.so.bak"), "foo.so");
+        assert_eq!(lib_name("libfoo.1.dylib"), "foo.1");
+
+        let targets = [Target {
+            name: "Foo::foo".into(),
+            location: Some("/opt/foo/lib/libfoo.a".into()),
+            libs: vec![
+                "/opt/bar/lib/libbar.so.1".into(),
+                "$<LINK_ONLY:/opt/baz/lib/libbaz.a>".into(),
+                "m".into(),
+            ],
+            ..Default::default()
+        }];
+        assert_eq!(archives(&targets), vec!["foo", "baz"]);
+    }
+}
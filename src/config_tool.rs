@@ -0,0 +1,51 @@
+// Probe libraries using `*-config` tools such as `llvm-config` or `curl-config`
+
+use std::{path::Path, process::Command};
+
+use crate::{
+    metadata::ConfigTool,
+    pc::{PcLibrary, Resolver},
+};
+
+/// Run `tool` and parse the flags it reports.
+pub(crate) fn probe(
+    tool: &ConfigTool,
+    manifest_dir: &Path,
+    resolver: &Resolver,
+) -> Result<PcLibrary, String> {
+    // Tools are looked for in `PATH` unless a path is given
+    let program = if tool.name.contains(['/', '\\']) {
+        manifest_dir.join(&tool.name)
+    } else {
+        tool.name.clone().into()
+    };
+
+    let mut flags = run(&program, &tool.cflags)?;
+    flags.push('\n');
+    flags.push_str(&run(&program, &tool.libs)?);
+
+    let mut lib = resolver.parse_output(&flags)?;
+    lib.version = run(&program, &tool.version)?.trim().to_string();
+    Ok(lib)
+}
+
+fn run(program: &Path, args: &[String]) -> Result<String, String> {
+    if args.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    let output = cmd
+        .output()
+        .map_err(|e| format!("failed to run {cmd:?}: {e}"))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(format!(
+            "{cmd:?} failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ))
+    }
+}
@@ -0,0 +1,215 @@
+// Settings of the target when cross-compiling, read from the environment of the `Config`
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{pc, EnvVariables, EnvVariablesExt};
+
+/// Whether the target differs from the host.
+pub(crate) fn is_cross(env: &EnvVariables) -> bool {
+    match (env.get("TARGET"), env.get("HOST")) {
+        (Some(target), Some(host)) => target != host,
+        _ => false,
+    }
+}
+
+/// Read `var` for the target, as the `pkg-config` crate does: `${var}_${TARGET}`,
+/// `${var}_${TARGET}` with underscores, `TARGET_${var}` or `HOST_${var}`, then `${var}`.
+pub(crate) fn targeted_var(env: &EnvVariables, var: &str) -> Option<String> {
+    targeted_var_names(env, var)
+        .iter()
+        .find_map(|name| env.get(name.as_str()))
+}
+
+/// The names of `var` read by [`targeted_var`], in order of precedence.
+pub(crate) fn targeted_var_names(env: &EnvVariables, var: &str) -> Vec<String> {
+    match (env.get("TARGET"), env.get("HOST")) {
+        (Some(target), Some(host)) => {
+            let kind = if host == target { "HOST" } else { "TARGET" };
+            vec![
+                format!("{var}_{target}"),
+                format!("{var}_{}", target.replace('-', "_")),
+                format!("{kind}_{var}"),
+                var.to_string(),
+            ]
+        }
+        _ => vec![var.to_string()],
+    }
+}
+
+/// Suffixes of the `SYSTEM_DEPS_*` overrides defined for a single target, in order of
+/// precedence: the target with underscores, then `TARGET`, or `HOST` when not cross-compiling.
+pub(crate) fn var_suffixes(env: &EnvVariables) -> Vec<String> {
+    match env.get("TARGET") {
+        Some(target) => {
+            let kind = if is_cross(env) { "TARGET" } else { "HOST" };
+            vec![target.replace('-', "_"), kind.to_string()]
+        }
+        None => Vec::new(),
+    }
+}
+
+/// The variables defining the sysroot of the target, in order of precedence.
+pub(crate) fn sysroot_var_names(env: &EnvVariables) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Some(target) = env.get("TARGET") {
+        let target = target.replace('-', "_");
+        names.push(format!("SYSTEM_DEPS_SYSROOT_{}", target.to_uppercase()));
+        names.push(format!("SYSTEM_DEPS_SYSROOT_{target}"));
+    }
+    names.push("SYSTEM_DEPS_SYSROOT".to_string());
+    names
+}
+
+/// The sysroot of the target: `SYSTEM_DEPS_SYSROOT_$TARGET`, `SYSTEM_DEPS_SYSROOT`,
+/// `PKG_CONFIG_SYSROOT_DIR` for the target, then `SYSROOT`.
+pub(crate) fn sysroot(env: &EnvVariables) -> Option<PathBuf> {
+    sysroot_var_names(env)
+        .iter()
+        .find_map(|var| env.get(var.as_str()))
+        .or_else(|| targeted_var(env, "PKG_CONFIG_SYSROOT_DIR"))
+        .or_else(|| env.get("SYSROOT"))
+        .filter(|s| !s.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Prefix the absolute `path` with `sysroot`, unless it is already in it.
+pub(crate) fn with_sysroot(sysroot: Option<&Path>, path: &Path) -> PathBuf {
+    match sysroot {
+        Some(sysroot) if path.has_root() && !path.starts_with(sysroot) => {
+            let mut s = sysroot.as_os_str().to_os_string();
+            s.push(path);
+            PathBuf::from(s)
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The `pkg-config` binary to use for the target: `PKG_CONFIG` for the target or,
+/// when cross-compiling, a triple-prefixed one such as `aarch64-linux-gnu-pkg-config`
+/// if it is in `PATH`.
+pub(crate) fn pkg_config_exe(env: &EnvVariables) -> Option<PathBuf> {
+    if let Some(exe) = targeted_var(env, "PKG_CONFIG") {
+        return Some(PathBuf::from(exe));
+    }
+    if !is_cross(env) {
+        return None;
+    }
+
+    let target = env.get("TARGET")?;
+    let paths = env.get("PATH").unwrap_or_default();
+    let names = std::iter::once(target.clone())
+        .chain(pc::multiarch(&target))
+        .map(|prefix| format!("{prefix}-pkg-config{}", env::consts::EXE_SUFFIX))
+        .collect::<Vec<_>>();
+
+    names.iter().find_map(|name| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join(name))
+            .find(|p| p.is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn env(vars: &[(&'static str, &str)]) -> EnvVariables {
+        EnvVariables::Mock(
+            vars.iter()
+                .map(|(k, v)| (*k, v.to_string()))
+                .collect::<HashMap<_, _>>(),
+        )
+    }
+
+    #[test]
+    fn targeted() {
+        let cross = [
+            ("TARGET", "aarch64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+        ];
+        let var = |vars: &[(&'static str, &'static str)]| {
+            let vars = cross.iter().chain(vars).copied().collect::<Vec<_>>();
+            targeted_var(&env(&vars), "PKG_CONFIG_PATH")
+        };
+
+        assert_eq!(var(&[]), None);
+        assert_eq!(var(&[("PKG_CONFIG_PATH", "a")]).unwrap(), "a");
+        assert_eq!(
+            var(&[("PKG_CONFIG_PATH", "a"), ("TARGET_PKG_CONFIG_PATH", "b")]).unwrap(),
+            "b"
+        );
+        assert_eq!(
+            var(&[
+                ("TARGET_PKG_CONFIG_PATH", "b"),
+                ("PKG_CONFIG_PATH_aarch64_unknown_linux_gnu", "c")
+            ])
+            .unwrap(),
+            "c"
+        );
+        assert_eq!(
+            var(&[
+                ("PKG_CONFIG_PATH_aarch64_unknown_linux_gnu", "c"),
+                ("PKG_CONFIG_PATH_aarch64-unknown-linux-gnu", "d")
+            ])
+            .unwrap(),
+            "d"
+        );
+        // HOST_ variables are used when not cross-compiling
+        assert_eq!(
+            targeted_var(
+                &env(&[
+                    ("TARGET", "x86_64-unknown-linux-gnu"),
+                    ("HOST", "x86_64-unknown-linux-gnu"),
+                    ("HOST_PKG_CONFIG_PATH", "e"),
+                ]),
+                "PKG_CONFIG_PATH"
+            )
+            .unwrap(),
+            "e"
+        );
+    }
+
+    #[test]
+    fn sysroot_paths() {
+        let target = ("TARGET", "aarch64-unknown-linux-gnu");
+        assert_eq!(sysroot(&env(&[target])), None);
+        assert_eq!(
+            sysroot(&env(&[
+                target,
+                ("SYSROOT", "/a"),
+                ("SYSTEM_DEPS_SYSROOT", "/b")
+            ]))
+            .unwrap(),
+            Path::new("/b")
+        );
+        assert_eq!(
+            sysroot(&env(&[
+                target,
+                ("SYSTEM_DEPS_SYSROOT", "/b"),
+                ("SYSTEM_DEPS_SYSROOT_AARCH64_UNKNOWN_LINUX_GNU", "/c")
+            ]))
+            .unwrap(),
+            Path::new("/c")
+        );
+
+        let root = Some(Path::new("/sysroot"));
+        assert_eq!(
+            with_sysroot(root, Path::new("/usr/lib")),
+            Path::new("/sysroot/usr/lib")
+        );
+        assert_eq!(
+            with_sysroot(root, Path::new("/sysroot/usr/lib")),
+            Path::new("/sysroot/usr/lib")
+        );
+        assert_eq!(with_sysroot(root, Path::new("lib")), Path::new("lib"));
+        assert_eq!(
+            with_sysroot(None, Path::new("/usr/lib")),
+            Path::new("/usr/lib")
+        );
+    }
+}
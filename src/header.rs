@@ -0,0 +1,83 @@
+// Read the version of a library from the macros defined in one of its headers
+
+use std::{fs, path::PathBuf};
+
+use crate::metadata::VersionHeader;
+
+/// Look for `header.file` in `include_paths` and build the version from its macros.
+pub(crate) fn read_version(
+    header: &VersionHeader,
+    include_paths: &[PathBuf],
+) -> Result<String, String> {
+    let path = include_paths
+        .iter()
+        .map(|dir| dir.join(&header.file))
+        .find(|p| p.is_file())
+        .ok_or_else(|| {
+            format!(
+                "{} not found in include paths {include_paths:?}",
+                header.file
+            )
+        })?;
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+
+    let mut version = Vec::new();
+    for name in std::iter::once(&header.major)
+        .chain(std::iter::once(&header.minor))
+        .chain(header.micro.as_ref())
+    {
+        let value = find_define(&content, name)
+            .ok_or_else(|| format!("{name} is not defined as a number in {}", path.display()))?;
+        version.push(value);
+    }
+
+    Ok(version.join("."))
+}
+
+// Parse `#define NAME 12` lines, accepting values such as `(12)` or `12U`
+fn find_define(content: &str, name: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let line = line.trim_start().strip_prefix('#')?.trim_start();
+        let mut words = line.strip_prefix("define")?.split_whitespace();
+        if words.next()? != name {
+            return None;
+        }
+
+        let value = words
+            .next()?
+            .trim_matches(|c| c == '(' || c == ')')
+            .trim_end_matches(['u', 'U', 'l', 'L']);
+        if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) {
+            Some(value.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn define() {
+        let content = r#"
+#ifndef FOO_H
+#define FOO_H
+#define FOO_MAJOR 1
+#  define FOO_MINOR (12)
+#define FOO_MICRO 3U /* micro */
+#define FOO_NAME "foo"
+#endif
+"#;
+
+        assert_eq!(find_define(content, "FOO_MAJOR").as_deref(), Some("1"));
+        assert_eq!(find_define(content, "FOO_MINOR").as_deref(), Some("12"));
+        assert_eq!(find_define(content, "FOO_MICRO").as_deref(), Some("3"));
+        assert_eq!(find_define(content, "FOO_NAME"), None);
+        assert_eq!(find_define(content, "FOO_H"), None);
+        assert_eq!(find_define(content, "FOO"), None);
+    }
+}
@@ -10,7 +10,7 @@
 //!
 //! ```toml
 //! [build-dependencies]
-//! system-deps = "7.0"
+//! system-deps = "8.0"
 //! ```
 //!
 //! Then, to declare a dependency on `testlib >= 1.2`
@@ -35,8 +35,12 @@
 //!
 //!   * "1.2" or ">= 1.2": At least version 1.2
 //!   * ">= 1.2, < 2.0": At least version 1.2 but less than version 2.0
+//!   * "= 1.2.3": Exactly version 1.2.3
+//!   * ">= 2.60, != 2.62.1": At least version 2.60 but not version 2.62.1
 //!
-//! In the future more complicated version expressions might be supported.
+//! A version requirement is a comma separated list of comparators which all have to be satisfied.
+//! The supported operators are `=`, `!=`, `<`, `<=`, `>` and `>=`. A version without operator
+//! is equivalent to `>=`.
 //!
 //! Note that these versions are not interpreted according to the semver rules, but based on the
 //! rules defined by pkg-config.
@@ -70,6 +74,13 @@
 //! println!("found test-data");
 //! ```
 //!
+//! These cfgs, as well as the other ones set by `system-deps`, are declared using
+//! [`rustc-check-cfg`](https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-check-cfg)
+//! for all the dependencies, even the ones which were not found or are not used on this target,
+//! so `rustc` does not warn about unexpected cfgs.
+//! These declarations use the `cargo::` syntax, unless the crate declares a `rust-version` older
+//! than 1.77 which does not support it, in which case the `cargo:` one is used.
+//!
 //! # Overriding library name
 //! `toml` keys cannot contain dot characters so if your library name does, you can define it using the `name` field:
 //!
@@ -98,6 +109,143 @@
 //! v2 = { version = "2.0", fallback-names = ["libfoo2"] }
 //! ```
 //!
+//! # Alternatives
+//!
+//! Some dependencies can be satisfied by different libraries, each with its own version scheme.
+//! Such libraries can be listed using `alternatives`, which are tried in order:
+//!
+//! ```toml
+//! [package.metadata.system-deps.ssl]
+//! alternatives = [
+//!     { name = "openssl", version = "1.1" },
+//!     { name = "libressl", version = "3.0" },
+//! ]
+//! ```
+//!
+//! The `name` and `fallback-names` of the dependency are ignored when alternatives are defined.
+//! As each alternative has its own version scheme, they cannot be combined with
+//! [feature versions](#feature-versions) nor be [built internally](#internally-build-system-libraries).
+//!
+//! The alternative which has been found is stored in [`Library::alternative`] and `system-deps`
+//! sets the cfg `system_deps_$DEP_alt_$NAME`, where `$NAME` is the alternative name in snake_case:
+//!
+//! ```
+//! #[cfg(system_deps_ssl_alt_libressl)]
+//! fn tls_backend() -> &'static str {
+//!     "libressl"
+//! }
+//!
+//! #[cfg(not(system_deps_ssl_alt_libressl))]
+//! fn tls_backend() -> &'static str {
+//!     "openssl"
+//! }
+//! ```
+//!
+//! # Sanity checks
+//!
+//! `pkg-config` may report a library which is not actually usable, because of a broken `-dev` package
+//! or a stale `.pc` file. Headers and symbols which are required can be listed using `check-headers`
+//! and `check-symbols`:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! foo = { version = "1.0", check-headers = ["foo/foo.h"], check-symbols = ["foo_init"] }
+//! ```
+//!
+//! Once the library has been found by `pkg-config`, `system-deps` compiles a small C program
+//! including the headers and links another one using the symbols, with the compiler and flags of
+//! the target defined in `$CC_$TARGET`, `$TARGET_CC` or `$CC` and `$CFLAGS_$TARGET`,
+//! `$TARGET_CFLAGS` or `$CFLAGS` as the [cc](https://docs.rs/cc) crate does (`cc` by default),
+//! and the sysroot if any. If one of those fails, probing fails with [`Error::CheckFailed`],
+//! or the dependency is skipped if it is optional.
+//!
+//! # Config tools
+//!
+//! Some libraries report their flags using a `*-config` tool, such as `llvm-config`, rather than
+//! a `.pc` file. Such a tool can be used instead of `pkg-config` using `config-tool`:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! curl = { version = "7.60", config-tool = "curl-config" }
+//! llvm = { version = "15", config-tool = { name = "llvm-config", libs = ["--ldflags", "--libs", "--system-libs"] } }
+//! ```
+//!
+//! The tool is run with the `version` (`--version` by default), `cflags` (`--cflags`) and `libs`
+//! (`--libs`) arguments, and the `-I`, `-L`, `-l`, `-D` and `-Wl` flags it prints are used to fill
+//! the [`Library`], which has [`Source::ConfigTool`] as source.
+//! The version requirement is checked unless `version` is empty, in which case the version is
+//! unknown and the requirement must not have a lower bound, unless `allow-unknown-version = true`.
+//! Names containing a path separator are relative to the directory of `Cargo.toml`,
+//! others are looked for in `PATH`.
+//! The environment variables described in [Overriding build flags](#overriding-build-flags)
+//! still apply on top of the flags reported by the tool.
+//!
+//! # CMake packages
+//!
+//! Libraries shipping only a CMake package config file, such as `FooConfig.cmake`, can be found
+//! using `cmake` instead of `pkg-config`:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! foo = { version = "2.1", cmake = { package = "Foo", components = ["core", "gui"] } }
+//! ```
+//!
+//! `system-deps` generates a CMake project calling `find_package()` in `$OUT_DIR` and configures
+//! it using `$CMAKE` (`cmake` by default, version 3.21 or later is required).
+//! The include directories, link libraries and compile definitions of all the imported targets
+//! are used to fill the [`Library`], which has [`Source::CMake`] as source.
+//! The version requirement is checked against `Foo_VERSION`. If the package does not define it,
+//! the requirement must not have a lower bound, unless `allow-unknown-version = true`.
+//! `CMAKE_PREFIX_PATH` can be used to find packages installed in non-standard locations and
+//! the environment variables described in [Overriding build flags](#overriding-build-flags)
+//! still apply.
+//!
+//! Libraries installed as static archives are reported as having a static version, so packages
+//! only providing archives are linked statically whatever the [link kind](#static-linking).
+//! When cross-compiling, a CMake toolchain file describing the target has to be defined using
+//! `CMAKE_TOOLCHAIN_FILE_$TARGET`, `TARGET_CMAKE_TOOLCHAIN_FILE` or `CMAKE_TOOLCHAIN_FILE`.
+//!
+//! # Searching library directories
+//!
+//! Some systems do not ship `.pc` files for all their libraries. The library files and headers
+//! to look for if `pkg-config` fails can be defined using `search`:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! foo = { version = "1.0", search = { libs = ["foo"], headers = ["foo.h"] } }
+//! ```
+//!
+//! The libraries (such as `libfoo.so` or `libfoo.a`) are looked for in the directories listed in
+//! `SYSTEM_DEPS_$NAME_SEARCH_NATIVE` and `LIBRARY_PATH`, then in `/usr/local/lib` and the system
+//! library directories, including the multiarch ones such as `/usr/lib/x86_64-linux-gnu`.
+//! Headers are looked for in `SYSTEM_DEPS_$NAME_INCLUDE`, `CPATH`, `C_INCLUDE_PATH`,
+//! `/usr/local/include` and `/usr/include`.
+//! The resulting [`Library`] has [`Source::Search`] as source.
+//!
+//! The version of a library found this way is unknown, so the version requirement is not
+//! checked unless a `version-header` is defined in one of the searched headers directories.
+//! For this reason, the library files are only looked for if `pkg-config` did not find the
+//! library at all, or if a `version-header` is defined when it found a version which is too old.
+//!
+//! # vcpkg
+//!
+//! Libraries installed by [vcpkg](https://vcpkg.io) are looked for first if `VCPKG_INSTALLED_DIR`
+//! or `VCPKG_ROOT` is defined. The installed tree is `$VCPKG_INSTALLED_DIR`, the `vcpkg_installed`
+//! directory next to `Cargo.toml` when using a vcpkg manifest, or `$VCPKG_ROOT/installed`.
+//! The triplet is `$VCPKG_DEFAULT_TRIPLET` or the default one for the target, such as `x64-linux`
+//! or `x64-windows-static` if the `crt-static` target feature is enabled.
+//!
+//! The `.pc` files of the triplet are used if the port installs them, otherwise the library is
+//! looked for in its `lib` directory as described in
+//! [Searching library directories](#searching-library-directories). A library whose `.pc` file
+//! reports a version which is too old is not looked for this way.
+//! Debug libraries, from the `debug` directory, are used if `$PROFILE` is `debug`.
+//! Libraries of static triplets, such as the Linux and macOS default ones, are linked statically.
+//! The resulting [`Library`] has [`Source::Vcpkg`] as source.
+//! Libraries not installed by vcpkg are looked for using `pkg-config`, as are the ones vcpkg
+//! installed but which cannot be used, such as a version which is too old, in which case a
+//! `cargo:warning` is emitted.
+//!
 //! # Feature versions
 //!
 //! `-sys` crates willing to support various versions of their underlying system libraries
@@ -128,6 +276,49 @@
 //! v1_18 = { version = "1.18", name = "gstreamer-gl-egl-1.0" }
 //! ```
 //!
+//! # Version cfgs
+//!
+//! The version of the library actually found may be higher than the one required.
+//! `system-deps` exports a cfg `system_deps_$DEP_version_$VERSION` for each version in `version-cfgs`
+//! reached by the library found, with the dots of `$VERSION` replaced by underscores:
+//!
+//! ```toml
+//! [package.metadata.system-deps.gstreamer_1_0]
+//! name = "gstreamer-1.0"
+//! version = "1.14"
+//! version-cfgs = ["1.18", "1.20"]
+//! ```
+//!
+//! ```
+//! #[cfg(system_deps_gstreamer_1_0_version_1_18)]
+//! println!("gstreamer 1.18 or later found");
+//! ```
+//!
+//! If `version-cfgs` is not defined, the versions required by the [feature versions](#feature-versions)
+//! are used instead, such as `1.2`, `1.4` and `1.6` in the example above.
+//!
+//! # Workspace dependencies
+//!
+//! Dependencies shared by several crates of a workspace can be declared once in the workspace root `Cargo.toml`:
+//!
+//! ```toml
+//! [workspace.metadata.system-deps]
+//! glib = { name = "glib-2.0", version = "2.64" }
+//! gio = { name = "gio-2.0", version = "2.64" }
+//! ```
+//!
+//! Members can then inherit them, optionally overriding the `version`, `feature`, `optional`, `cfg`,
+//! `link` or `build-internal` settings:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! glib = { workspace = true }
+//! gio = { workspace = true, version = "2.70", feature = "gio" }
+//! ```
+//!
+//! The workspace root is found using the `package.workspace` key if defined, or by looking for a `Cargo.toml`
+//! with a `[workspace]` table in the parent directories.
+//!
 //! # Target specific dependencies
 //!
 //! You can define target specific dependencies:
@@ -143,15 +334,39 @@
 //!
 //! See [the Rust documentation](https://doc.rust-lang.org/reference/conditional-compilation.html)
 //! for the exact syntax.
-//! Currently, those keys are supported:
-//! - `target_arch`
-//! - `target_endian`
-//! - `target_env`
-//! - `target_family`
-//! - `target_os`
-//! - `target_pointer_width`
-//! - `target_vendor`
-//! - `unix` and `windows`
+//!
+//! Expressions are evaluated using the `CARGO_CFG_*` and `CARGO_FEATURE_*`
+//! [environment variables](https://doc.rust-lang.org/cargo/reference/environment-variables.html#environment-variables-cargo-sets-for-build-scripts)
+//! defined by Cargo for build scripts, so any cfg known by Cargo can be used, including `feature`,
+//! `target_feature`, `panic` and custom `--cfg` flags:
+//!
+//! ```toml
+//! [package.metadata.system-deps.'cfg(all(unix, feature = "x11"))']
+//! testdata = "1"
+//! [package.metadata.system-deps.'cfg(not(target_feature = "crt-static"))']
+//! testlib = "1"
+//! ```
+//!
+//! Target keys such as `target_os` are evaluated using the `TARGET` triple if Cargo did not define the corresponding variable.
+//!
+//! A dependency can also be restricted to some targets using its `cfg` key:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! testdata = { version = "1", cfg = 'target_os = "linux"' }
+//! ```
+//!
+//! Some settings of a dependency can be overridden for specific targets using nested `cfg()` tables.
+//! Supported settings are `name`, `version`, `fallback-names` and `optional`.
+//! If several tables match, they are applied in the alphabetical order of their `cfg()` expressions.
+//!
+//! ```toml
+//! [package.metadata.system-deps.foo]
+//! name = "libfoo"
+//! version = "1.0"
+//! 'cfg(target_os = "freebsd")' = { name = "foo-1", version = "1.2" }
+//! 'cfg(windows)' = { fallback-names = ["foo"] }
+//! ```
 //!
 //! # Overriding build flags
 //!
@@ -167,9 +382,60 @@
 //! With `$NAME` being the upper case name of the key defining the dependency in `Cargo.toml`.
 //! For example `SYSTEM_DEPS_TESTLIB_SEARCH_NATIVE=/opt/lib` could be used to override a dependency named `testlib`.
 //!
+//! These variables, as well as `SYSTEM_DEPS_$NAME_NO_PKG_CONFIG`, `SYSTEM_DEPS_$NAME_LDFLAGS`, `SYSTEM_DEPS_$NAME_LINK`,
+//! `SYSTEM_DEPS_$NAME_LINK_MODIFIERS`, `SYSTEM_DEPS_$NAME_BUILD_INTERNAL` and their global versions, can be defined for a single target by adding
+//! the target with underscores as suffix, then `_TARGET` when cross-compiling or `_HOST` otherwise.
+//! For example `SYSTEM_DEPS_TESTLIB_LIB_x86_64_unknown_linux_gnu` takes precedence over
+//! `SYSTEM_DEPS_TESTLIB_LIB_HOST`, which takes precedence over `SYSTEM_DEPS_TESTLIB_LIB`.
+//!
 //! One can also define the environment variable `SYSTEM_DEPS_$NAME_NO_PKG_CONFIG` to fully disable `pkg-config` lookup
 //! for the given dependency. In this case at least SYSTEM_DEPS_$NAME_LIB or SYSTEM_DEPS_$NAME_LIB_FRAMEWORK should be defined as well.
 //!
+//! The version of such dependencies is unknown and so cannot be checked, unless it can be read from one of their headers.
+//! The header is looked for in the paths defined by `SYSTEM_DEPS_$NAME_INCLUDE`, then in the same headers directories
+//! as when [searching library directories](#searching-library-directories). The `micro` macro is optional.
+//! If the header cannot be read or the version is too old, optional dependencies are skipped:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! foo = { version = "1.2", version-header = { file = "foo/version.h", major = "FOO_MAJOR", minor = "FOO_MINOR", micro = "FOO_MICRO" } }
+//! ```
+//!
+//! # Reading the metadata
+//!
+//! Other tools can read the dependencies declared in `Cargo.toml`, without probing for them,
+//! using [`metadata::parse`].
+//!
+//! # Dependencies of other -sys crates
+//!
+//! If the `-sys` crate defines the [`links`](https://doc.rust-lang.org/cargo/reference/build-scripts.html#the-links-manifest-key)
+//! key in its `Cargo.toml`, the dependencies found by [`Config::probe`] are exported to the build scripts of the crates
+//! depending on it, using `DEP_$LINKS_SYSTEM_DEPS_$NAME_*` environment variables such as `DEP_GLIB_SYSTEM_DEPS_GLIB_2_0_VERSION`.
+//! The variables hold the name, version, source, static linking, include paths, link paths, libraries, frameworks,
+//! defines, linker flags and `.pc` file of each dependency. Lists are separated by spaces, and the arguments of each
+//! linker flag by commas, those characters and backslashes being escaped with a backslash when part of an item.
+//!
+//! They can be read back using [`Dependencies::from_dep_env`]:
+//!
+//! ```should_panic
+//! let glib = system_deps::Dependencies::from_dep_env("glib").unwrap();
+//! let version = &glib.get_by_name("glib_2_0").unwrap().version;
+//! ```
+//!
+//! A dependency can also reuse the library found by another `-sys` crate instead of probing for it again,
+//! using the `inherit` setting with the `links` key of that crate:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! glib_2_0 = { version = "2.56", inherit = { links = "glib" } }
+//! ```
+//!
+//! The dependency is looked for using the same key in the `Cargo.toml` of the other crate, unless another key is defined
+//! using `inherit = { links = "glib", dependency = "glib" }`. As the other crate already emits the link flags of the library,
+//! only its include paths are exported. The resulting [`Library`] has [`Source::Inherited`] as source.
+//! If the dependency is `optional`, it is skipped when the other crate didn't find it, didn't export its
+//! dependencies or found a version not satisfying `version`.
+//!
 //! # Internally build system libraries
 //!
 //! `-sys` crates can provide support for building and statically link their underlying system library as part of their build process.
@@ -197,11 +463,137 @@
 //! You can also use the `SYSTEM_DEPS_BUILD_INTERNAL` environment variable with the same values
 //! defining the behavior for all the dependencies which don't have `SYSTEM_DEPS_$NAME_BUILD_INTERNAL` defined.
 //!
+//! The default behavior of a dependency can be defined in its metadata using `build-internal`.
+//! Environment variables still take precedence:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! zstd = { version = "1.5", build-internal = "always" }
+//! ```
+//!
+//! # Custom probes
+//!
+//! Build scripts can register their own backends to find system libraries by implementing the
+//! [`Probe`] trait:
+//!
+//! ```should_panic
+//! use system_deps::{DependencyRequest, Library, Probe, ProbeError};
+//!
+//! struct MyProbe;
+//!
+//! impl Probe for MyProbe {
+//!     fn probe(&self, dep: &DependencyRequest) -> Result<Option<Library>, ProbeError> {
+//!         // Look for `dep.name` here, returning `Ok(None)` if it is not found
+//!         Ok(None)
+//!     }
+//! }
+//!
+//! fn main() {
+//!     system_deps::Config::new()
+//!         .add_probe("foo", MyProbe)
+//!         .probe()
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! The ways used to find a dependency, and their order, are then defined using `sources`:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! testlib = { version = "1.2", sources = ["env", "pkg-config", "custom:foo", "internal"] }
+//! ```
+//!
+//! - `env`: the flags defined using `SYSTEM_DEPS_$NAME_LIB` or `SYSTEM_DEPS_$NAME_LIB_FRAMEWORK`,
+//!   see [Overriding build flags](#overriding-build-flags);
+//! - `pkg-config`: `pkg-config`, or the backend defined in the metadata of the dependency;
+//! - `custom:$NAME`: the probe registered using [`Config::add_probe`] with this name;
+//! - `internal`: the closure registered using [`Config::add_build_internal`].
+//!
+//! The first source finding the library is used. If they all fail, [`Error::SourcesFailed`]
+//! reports the error raised by each of them.
+//! `SYSTEM_DEPS_$NAME_NO_PKG_CONFIG` and `SYSTEM_DEPS_$NAME_BUILD_INTERNAL` still take precedence.
+//!
 //! # Static linking
 //!
 //! By default all libraries are dynamically linked, except when build internally as [described above](#internally-build-system-libraries).
 //! Libraries can be statically linked by defining the environment variable `SYSTEM_DEPS_$NAME_LINK=static`.
 //! You can also use `SYSTEM_DEPS_LINK=static` to statically link all the libraries.
+//!
+//! The default link kind of a dependency can be defined in its metadata using `link`,
+//! either `static`, `dynamic` or `prefer-static`. The latter links statically only if static
+//! versions of all the libraries of the dependency are available, and probes it again without
+//! its private libraries otherwise. Libraries defined using `SYSTEM_DEPS_$NAME_LIB` are linked dynamically.
+//!
+//! `SYSTEM_DEPS_$NAME_LINK` and `SYSTEM_DEPS_LINK` accept the same values and take precedence.
+//! A dependency is linked statically if either of them is `static`:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! zstd = { version = "1.5", link = "static" }
+//! ```
+//!
+//! # Link modifiers
+//!
+//! [Modifiers](https://doc.rust-lang.org/rustc/command-line-arguments.html#option-l-link-lib)
+//! such as `+whole-archive`, `-bundle`, `+as-needed` or `+verbatim` can be passed to
+//! `rustc-link-lib` using `link-modifiers` in the metadata, either for all the libraries
+//! of the dependency or for some of them.
+//! A library can also be given as `NAME:RENAME`, for `#[link(name = "NAME")]` attributes to link with `RENAME`:
+//!
+//! ```toml
+//! [package.metadata.system-deps]
+//! foo = { version = "1.0", link = "static", link-modifiers = ["+whole-archive"] }
+//! bar = { version = "2.0", link-modifiers = { "bar:bar-2" = ["+verbatim"] } }
+//! ```
+//!
+//! The modifiers can be overridden using `SYSTEM_DEPS_$NAME_LINK_MODIFIERS`, listing
+//! the modifiers of all the libraries, such as `+whole-archive,-bundle`, or those of a single one,
+//! such as `+verbatim=bar:bar-2`, separated by spaces.
+//! The `bundle` and `whole-archive` modifiers are ignored for the libraries which are linked dynamically,
+//! such as when `prefer-static` could not find their static version,
+//! and `as-needed` is ignored for the libraries which are linked statically.
+//!
+//! # pkg-config backends
+//!
+//! By default `system-deps` runs the `pkg-config` binary to resolve dependencies.
+//! It also provides a built-in resolver reading the `.pc` files directly, for systems where
+//! neither `pkg-config` nor `pkgconf` is available.
+//! It looks for `.pc` files in `PKG_CONFIG_PATH` and `PKG_CONFIG_LIBDIR` (or the default system paths if not defined),
+//! resolves `Requires` and `Requires.private` recursively and prefixes paths with `PKG_CONFIG_SYSROOT_DIR` if defined.
+//! The default paths include the usual Linux and BSD directories, as well as Homebrew and MacPorts
+//! ones on macOS. There are none on Windows, where `PKG_CONFIG_PATH` has to be defined.
+//!
+//! The built-in resolver can be selected using [`Config::pkg_config_backend`]:
+//!
+//! ```should_panic
+//! fn main() {
+//!     system_deps::Config::new()
+//!         .pkg_config_backend(system_deps::PkgConfigBackend::Builtin)
+//!         .probe()
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! or by defining `SYSTEM_DEPS_PKG_CONFIG_BACKEND=builtin`, which takes precedence.
+//! `SYSTEM_DEPS_PKG_CONFIG_BACKEND=binary` can be used to force using the `pkg-config` binary.
+//!
+//! # Cross-compilation
+//!
+//! When `TARGET` differs from `HOST`, libraries are looked for in the sysroot of the target,
+//! defined using `SYSTEM_DEPS_SYSROOT_$TARGET` (such as `SYSTEM_DEPS_SYSROOT_AARCH64_UNKNOWN_LINUX_GNU`),
+//! `SYSTEM_DEPS_SYSROOT`, `PKG_CONFIG_SYSROOT_DIR` or `SYSROOT`.
+//! The include and library paths found in `.pc` files and the default system directories are
+//! prefixed with the sysroot.
+//!
+//! The `PKG_CONFIG`, `PKG_CONFIG_PATH` and `PKG_CONFIG_LIBDIR` variables are read for the target
+//! as the [`pkg-config`](https://docs.rs/pkg-config) crate does, using `PKG_CONFIG_PATH_aarch64-unknown-linux-gnu`,
+//! `PKG_CONFIG_PATH_aarch64_unknown_linux_gnu`, `TARGET_PKG_CONFIG_PATH` then `PKG_CONFIG_PATH`.
+//! If `PKG_CONFIG` is not defined, a `pkg-config` binary prefixed with the target,
+//! such as `aarch64-linux-gnu-pkg-config`, is used if found in `PATH`.
+//!
+//! Cross-compiling without a sysroot fails with [`Error::CrossCompilationNoSysroot`],
+//! unless `PKG_CONFIG_ALLOW_CROSS=1` or a `pkg-config` binary for the target, as described above,
+//! is used.
 
 #![deny(missing_docs)]
 
@@ -214,18 +606,30 @@ mod test;
 
 use heck::{ToShoutySnakeCase, ToSnakeCase};
 use std::{
+    cell::RefCell,
     collections::{BTreeMap, HashMap},
     env, fmt,
-    ops::RangeBounds,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-mod metadata;
+pub mod metadata;
 use metadata::MetaData;
 
+mod check;
+mod cmake;
+mod config_tool;
+mod cross;
+mod header;
+mod pc;
+mod search;
+mod vcpkg;
+mod version;
+use version::VersionReq;
+
 /// system-deps errors
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// pkg-config error
     PkgConfig(pkg_config::Error),
@@ -233,8 +637,9 @@ pub enum Error {
     BuildInternalClosureError(String, BuildInternalClosureError),
     /// Failed to read `Cargo.toml`
     FailToRead(String, std::io::Error),
-    /// Raised when an error is detected in the metadata defined in `Cargo.toml`
-    InvalidMetadata(String),
+    /// Raised when an error is detected in the metadata defined in `Cargo.toml`,
+    /// with the location of the faulty item in the file if known
+    InvalidMetadata(String, Option<MetadataLocation>),
     /// Raised when dependency defined manually using `SYSTEM_DEPS_$NAME_NO_PKG_CONFIG`
     /// did not define at least one lib using `SYSTEM_DEPS_$NAME_LIB` or
     /// `SYSTEM_DEPS_$NAME_LIB_FRAMEWORK`
@@ -250,8 +655,48 @@ pub enum Error {
     /// The library which has been build internally does not match the
     /// required version defined in `Cargo.toml`
     BuildInternalWrongVersion(String, String, String),
-    /// The `cfg()` expression used in `Cargo.toml` is currently not supported
+    /// The `cfg()` expression used in `Cargo.toml` cannot be evaluated with the
+    /// information provided by Cargo
     UnsupportedCfg(String),
+    /// The version of the library found by `pkg-config` does not match the
+    /// version requirement defined in `Cargo.toml`
+    UnsatisfiedVersion(String, String, String),
+    /// The library found on the system failed the `check-headers` or
+    /// `check-symbols` sanity checks defined in `Cargo.toml`
+    CheckFailed(String, String),
+    /// The version of the library could not be read from the `version-header`
+    /// defined in `Cargo.toml`
+    VersionHeader(String, String),
+    /// Error raised by the built-in `.pc` files resolver
+    PkgConfigBuiltin(String),
+    /// The `SYSTEM_DEPS_PKG_CONFIG_BACKEND` environment variable contained an
+    /// invalid value (allowed: `binary`, `builtin`)
+    PkgConfigBackendInvalid(String),
+    /// The libraries or headers listed in the `search` metadata of the
+    /// dependency could not be found
+    SearchFailed(String, String),
+    /// The `config-tool` defined in `Cargo.toml` failed
+    ConfigToolFailed(String, String),
+    /// The CMake package defined in `Cargo.toml` could not be found
+    CMakeFailed(String, String),
+    /// Error raised when running `pkg-config` for the target when cross-compiling
+    PkgConfigCommand(String),
+    /// Cross-compiling using `pkg-config` requires a sysroot, defined using
+    /// `SYSTEM_DEPS_SYSROOT_$TARGET` or `SYSTEM_DEPS_SYSROOT`
+    CrossCompilationNoSysroot(String),
+    /// The dependencies exported by a `-sys` crate could not be read by [`Dependencies::from_dep_env`]
+    DepEnv(String),
+    /// An environment variable in the form of `SYSTEM_DEPS_$NAME_LINK_MODIFIERS`
+    /// contained an invalid modifier
+    LinkModifiersInvalid(String),
+    /// An environment variable in the form of `SYSTEM_DEPS_$NAME_LINK`
+    /// contained an invalid value (allowed: `static`, `dynamic`, `prefer-static`)
+    LinkKindInvalid(String),
+    /// One of the probes registered using `Config::add_probe` failed
+    Probe(String, String, ProbeError),
+    /// None of the `sources` defined in `Cargo.toml` provided the library,
+    /// with the error raised by each of them
+    SourcesFailed(String, Vec<(ProbeSource, Error)>),
 }
 
 impl From<pkg_config::Error> for Error {
@@ -266,6 +711,7 @@ impl std::error::Error for Error {
             Self::PkgConfig(e) => Some(e),
             Self::BuildInternalClosureError(_, e) => Some(e),
             Self::FailToRead(_, e) => Some(e),
+            Self::Probe(_, _, e) => Some(e),
             _ => None,
         }
     }
@@ -277,7 +723,8 @@ impl fmt::Display for Error {
             Self::PkgConfig(e) => write!(f, "{e}"),
             Self::BuildInternalClosureError(s, e) => write!(f, "Failed to build {s}: {e}"),
             Self::FailToRead(s, _) => write!(f, "{s}"),
-            Self::InvalidMetadata(s) => write!(f, "{s}"),
+            Self::InvalidMetadata(s, None) => write!(f, "{s}"),
+            Self::InvalidMetadata(s, Some(l)) => write!(f, "{s}\n{l}"),
             Self::MissingLib(s) => write!(
                 f,
                 "You should define at least one lib using {} or {}",
@@ -288,19 +735,144 @@ impl fmt::Display for Error {
             Self::BuildInternalNoClosure(s1, s2) => {
                 write!(f, "Missing build internal closure for {s1} (version {s2})")
             }
-            Self::BuildInternalWrongVersion(s1, s2, s3) => write!(
+            Self::BuildInternalWrongVersion(s1, s2, s3) => {
+                write!(f, "Internally built {s1} {s2} but required version is {s3}")
+            }
+            Self::UnsupportedCfg(s) => write!(f, "Unsupported cfg() expression: {s}"),
+            Self::UnsatisfiedVersion(s1, s2, s3) => {
+                write!(f, "Found {s1} {s2} but required version is {s3}")
+            }
+            Self::CheckFailed(s1, s2) => write!(f, "Sanity check of {s1} failed: {s2}"),
+            Self::VersionHeader(s1, s2) => {
+                write!(
+                    f,
+                    "Failed to read the version of {s1} from its header: {s2}"
+                )
+            }
+            Self::PkgConfigBuiltin(s) => write!(f, "{s}"),
+            Self::PkgConfigBackendInvalid(s) => write!(f, "{s}"),
+            Self::SearchFailed(s1, s2) => write!(f, "Failed to find {s1}: {s2}"),
+            Self::CMakeFailed(s1, s2) => write!(f, "Failed to find {s1} using CMake: {s2}"),
+            Self::PkgConfigCommand(s) => write!(f, "{s}"),
+            Self::CrossCompilationNoSysroot(s) => write!(
                 f,
-                "Internally built {s1} {s2} but minimum required version is {s3}"
+                "Cross-compiling for {s} but no sysroot has been defined using \
+                SYSTEM_DEPS_SYSROOT_{} or SYSTEM_DEPS_SYSROOT \
+                (set PKG_CONFIG_ALLOW_CROSS=1 to use pkg-config as is)",
+                s.replace('-', "_").to_uppercase()
             ),
-            Self::UnsupportedCfg(s) => write!(f, "Unsupported cfg() expression: {s}"),
+            Self::DepEnv(s) => write!(f, "{s}"),
+            Self::LinkModifiersInvalid(s) => write!(f, "{s}"),
+            Self::LinkKindInvalid(s) => write!(f, "{s}"),
+            Self::Probe(s1, s2, e) => write!(f, "Probe {s2} failed to find {s1}: {e}"),
+            Self::SourcesFailed(s, errors) => {
+                write!(f, "Failed to find {s}:")?;
+                for (source, e) in errors {
+                    write!(f, "\n  {source}: {e}")?;
+                }
+                Ok(())
+            }
+            Self::ConfigToolFailed(s1, s2) => {
+                write!(
+                    f,
+                    "Failed to get the flags of {s1} from its config tool: {s2}"
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Location of an invalid item in a `Cargo.toml` file.
+///
+/// Its [`Display`](fmt::Display) implementation shows the faulty line with the item underlined.
+pub struct MetadataLocation {
+    /// Path of the `Cargo.toml` file
+    pub path: PathBuf,
+    /// Line of the item, starting from 1
+    pub line: usize,
+    /// Column of the item, starting from 1
+    pub column: usize,
+    /// Byte range of the item in the file
+    pub span: std::ops::Range<usize>,
+    /// Content of the line containing the item
+    pub source_line: String,
+}
+
+impl MetadataLocation {
+    fn new(path: &Path, source: &str, span: std::ops::Range<usize>) -> Self {
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+
+        Self {
+            path: path.to_path_buf(),
+            line: source[..line_start].matches('\n').count() + 1,
+            column: source[line_start..start].chars().count() + 1,
+            span,
+            source_line: source[line_start..line_end]
+                .trim_end_matches('\r')
+                .to_string(),
         }
     }
 }
 
+impl fmt::Display for MetadataLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line = self.line.to_string();
+        let pad = " ".repeat(line.len());
+        // Underline the item, or only its beginning if it spans over multiple lines.
+        // The span is in bytes while the column is in characters.
+        let mut len = 0;
+        let width = self
+            .source_line
+            .chars()
+            .skip(self.column - 1)
+            .take_while(|c| {
+                let inside = len < self.span.end - self.span.start;
+                len += c.len_utf8();
+                inside
+            })
+            .count()
+            .max(1);
+
+        writeln!(f, "{pad} |")?;
+        writeln!(f, "{line} | {}", self.source_line)?;
+        write!(
+            f,
+            "{pad} | {}{}",
+            " ".repeat(self.column - 1),
+            "^".repeat(width)
+        )
+    }
+}
+
 #[derive(Debug, Default)]
 /// All the system dependencies retrieved by [`Config::probe`].
 pub struct Dependencies {
     libs: BTreeMap<String, Library>,
+    // Suffixes of the overrides defined for the target, see `EnvVariable::names`
+    env_suffixes: Vec<String>,
+    // Other environment variables and the `.pc` files the dependencies depend on
+    env_vars: Vec<String>,
+    pc_files: Vec<PathBuf>,
+    // The keys of all the dependencies, whether they have been found or not, whose
+    // `SYSTEM_DEPS_$NAME_*` overrides may change the result
+    keys: Vec<String>,
+    // All the cfgs which can be set for the dependencies, whether they have been found or not
+    cfgs: Vec<String>,
+    // The version thresholds of each dependency and the cfg set if the version found reaches them
+    version_cfgs: HashMap<String, Vec<(String, String)>>,
+    // The modifiers of the libraries of each dependency, from the metadata or the environment
+    link_modifiers: HashMap<String, Vec<metadata::LinkModifiers>>,
+    // Whether the `cargo::` syntax can be used
+    double_colon: bool,
+    // Whether the crate defines `links`
+    links: bool,
+    // Problems met while probing, reported as `cargo:warning`
+    warnings: Vec<String>,
 }
 
 impl Dependencies {
@@ -313,6 +885,39 @@ impl Dependencies {
         self.libs.get(name)
     }
 
+    /// Retrieve the system dependencies found by the build script of a `-sys` crate this crate depends on.
+    ///
+    /// The `-sys` crate has to define the `links` key in its `Cargo.toml` and use [`Config::probe`],
+    /// which exports its dependencies to the build scripts of the crates depending on it
+    /// using `DEP_$LINKS_SYSTEM_DEPS*` environment variables.
+    ///
+    /// # Arguments
+    ///
+    /// * `links`: the value of the `links` key of the `-sys` crate
+    pub fn from_dep_env(links: &str) -> Result<Self, Error> {
+        Self::from_dep_env_with(&EnvVariables::Environment, links)
+    }
+
+    fn from_dep_env_with(env: &EnvVariables, links: &str) -> Result<Self, Error> {
+        let prefix = format!("DEP_{}_SYSTEM_DEPS", envify(links));
+        let names = env.get(prefix.as_str()).ok_or_else(|| {
+            Error::DepEnv(format!(
+                "{prefix} is not defined, the crate with `links = \"{links}\"` has to use system-deps"
+            ))
+        })?;
+
+        let mut libraries = Self::default();
+        for name in split_string(&names) {
+            let var_prefix = format!("{prefix}_{}", envify(&name));
+            let library =
+                Library::from_dep_env(|key| env.get(format!("{var_prefix}_{key}").as_str()))
+                    .map_err(|e| Error::DepEnv(format!("{var_prefix}: {e}")))?;
+            libraries.add(&name, library);
+        }
+
+        Ok(libraries)
+    }
+
     /// A vector listing all system dependencies in sorted (for build reproducibility) order.
     /// The first element of the tuple is the name of the `toml` key defining the
     /// dependency in `Cargo.toml`.
@@ -402,6 +1007,105 @@ impl Dependencies {
         v
     }
 
+    /// Write a Rust file defining a module for each system dependency, with constants describing the library found.
+    ///
+    /// Each module, named after the `toml` key defining the dependency in [snake_case](https://en.wikipedia.org/wiki/Snake_case),
+    /// defines `NAME`, the library name actually used, `VERSION` and its `MAJOR`, `MINOR` and `MICRO` components,
+    /// `STATIC` and a `defines` module with the [`Library::defines`] as `Option<&str>` constants.
+    /// Characters which are not valid in Rust identifiers are replaced by `_`, and identifiers starting
+    /// with a digit are prefixed by `_`. An [`std::io::ErrorKind::InvalidData`] error is returned if two
+    /// modules, or two defines of the same dependency, end up with the same identifier.
+    ///
+    /// ```no_run
+    /// // build.rs
+    /// fn main() {
+    ///     let deps = system_deps::Config::new().probe().unwrap();
+    ///     let out_dir = std::env::var_os("OUT_DIR").unwrap();
+    ///     deps.write_rust_module(std::path::Path::new(&out_dir).join("system_deps.rs"))
+    ///         .unwrap();
+    /// }
+    /// ```
+    ///
+    /// The file can then be included in the crate using `include!(concat!(env!("OUT_DIR"), "/system_deps.rs"));`,
+    /// and checked with `if (testlib::MAJOR, testlib::MINOR) >= (1, 2) { ... }`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: the file to write, usually in `$OUT_DIR`
+    pub fn write_rust_module(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let module = self
+            .rust_module()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, module)
+    }
+
+    fn rust_module(&self) -> Result<String, String> {
+        use std::fmt::Write;
+
+        // Identifiers which are not valid Rust ones are mangled
+        let ident = |s: &str| {
+            let s = s.replace(|c: char| !c.is_ascii_alphanumeric() && c != '_', "_");
+            if s.starts_with(|c: char| c.is_ascii_digit()) {
+                format!("_{s}")
+            } else {
+                s
+            }
+        };
+        // The names each identifier has been generated from, to detect collisions
+        let check_unique = |seen: &mut HashMap<String, String>, ident: &str, name: &str| match seen
+            .insert(ident.to_string(), name.to_string())
+        {
+            Some(other) => Err(format!(
+                "{other} and {name} are both written as the Rust identifier {ident}"
+            )),
+            None => Ok(()),
+        };
+
+        let mut out = String::from("// Generated by system-deps\n");
+        let mut modules = HashMap::new();
+        for (name, lib) in self.iter() {
+            let module = ident(&name.to_snake_case());
+            check_unique(&mut modules, &module, name)?;
+
+            let mut version = lib.version.split('.').map(|v| {
+                let digits = v.find(|c: char| !c.is_ascii_digit()).unwrap_or(v.len());
+                v[..digits].parse::<u32>().unwrap_or(0)
+            });
+            let mut defines = lib.defines.iter().collect::<Vec<_>>();
+            defines.sort();
+
+            writeln!(out, "\n#[allow(dead_code)]").unwrap();
+            writeln!(out, "pub mod {module} {{").unwrap();
+            writeln!(out, "    pub const NAME: &str = {:?};", lib.name).unwrap();
+            writeln!(out, "    pub const VERSION: &str = {:?};", lib.version).unwrap();
+            for component in ["MAJOR", "MINOR", "MICRO"] {
+                let value = version.next().unwrap_or(0);
+                writeln!(out, "    pub const {component}: u32 = {value};").unwrap();
+            }
+            writeln!(out, "    pub const STATIC: bool = {};", lib.statik).unwrap();
+            writeln!(out, "\n    #[allow(non_upper_case_globals)]").unwrap();
+            if defines.is_empty() {
+                writeln!(out, "    pub mod defines {{}}").unwrap();
+                writeln!(out, "}}").unwrap();
+                continue;
+            }
+            writeln!(out, "    pub mod defines {{").unwrap();
+            let mut consts = HashMap::new();
+            for (k, v) in defines {
+                let konst = ident(k);
+                check_unique(&mut consts, &konst, k).map_err(|e| format!("{name}: defines {e}"))?;
+                let value = match v {
+                    Some(v) => format!("Some({v:?})"),
+                    None => "None".to_string(),
+                };
+                writeln!(out, "        pub const {konst}: Option<&str> = {value};").unwrap();
+            }
+            writeln!(out, "    }}").unwrap();
+            writeln!(out, "}}").unwrap();
+        }
+        Ok(out)
+    }
+
     fn add(&mut self, name: &str, lib: Library) {
         self.libs.insert(name.to_string(), lib);
     }
@@ -415,9 +1119,7 @@ impl Dependencies {
                 lib.framework_paths = split_paths(&value);
             }
             if let Some(value) = env.get(&EnvVariable::new_lib(name)) {
-                let should_be_linked_statically = env
-                    .has_value(&EnvVariable::new_link(Some(name)), "static")
-                    || env.has_value(&EnvVariable::new_link(None), "static");
+                let should_be_linked_statically = lib.statik;
 
                 // If somebody manually mandates static linking, that is a
                 // clear intent. Let's just assume that a static lib is
@@ -448,6 +1150,10 @@ impl Dependencies {
         let mut flags = BuildFlags::new();
         let mut include_paths = Vec::new();
 
+        for warning in self.warnings.iter() {
+            flags.add(BuildFlag::Warning(warning.clone()));
+        }
+
         for (name, lib) in self.iter() {
             include_paths.extend(lib.include_paths.clone());
 
@@ -458,17 +1164,23 @@ impl Dependencies {
                 return Err(Error::MissingLib(name.to_string()));
             }
 
+            // The crate the library has been inherited from already links it
+            if lib.source == Source::Inherited {
+                continue;
+            }
+
             lib.link_paths
                 .iter()
                 .for_each(|l| flags.add(BuildFlag::SearchNative(l.to_string_lossy().to_string())));
             lib.framework_paths.iter().for_each(|f| {
                 flags.add(BuildFlag::SearchFramework(f.to_string_lossy().to_string()))
             });
+            let link_modifiers = self.link_modifiers.get(name).map(Vec::as_slice);
             lib.libs.iter().for_each(|l| {
-                flags.add(BuildFlag::Lib(
-                    l.name.clone(),
-                    lib.statik && l.is_static_available,
-                ))
+                let statik = lib.statik && l.is_static_available;
+                let (modifiers, link_name) =
+                    lib_link_modifiers(link_modifiers.unwrap_or_default(), &l.name, statik);
+                flags.add(BuildFlag::Lib(l.name.clone(), statik, modifiers, link_name))
             });
             lib.frameworks
                 .iter()
@@ -487,37 +1199,194 @@ impl Dependencies {
         }
 
         // Export cargo:rerun-if-env-changed instructions for all env variables affecting system-deps behaviour
-        flags.add(BuildFlag::RerunIfEnvChanged(
-            EnvVariable::new_build_internal(None),
-        ));
-        flags.add(BuildFlag::RerunIfEnvChanged(EnvVariable::new_link(None)));
-
-        for (name, _lib) in self.libs.iter() {
-            EnvVariable::set_rerun_if_changed_for_all_variants(&mut flags, name);
+        for var in self.env_vars.iter() {
+            flags.add(BuildFlag::RerunIfEnvChanged(var.clone()));
+        }
+        for (_name, lib) in self.iter() {
+            // Read by the `pkg-config` crate
+            if lib.source == Source::PkgConfig {
+                let var = envify(&lib.name);
+                flags.add(BuildFlag::RerunIfEnvChanged(format!("{var}_NO_PKG_CONFIG")));
+            }
+        }
+        for file in self.pc_files.iter() {
+            flags.add(BuildFlag::RerunIfChanged(file.clone()));
+        }
+        EnvVariable::new_build_internal(None).add_rerun_if_changed(&mut flags, &self.env_suffixes);
+        EnvVariable::new_link(None).add_rerun_if_changed(&mut flags, &self.env_suffixes);
+
+        let others = self.libs.keys().filter(|name| !self.keys.contains(name));
+        for name in self.keys.iter().chain(others) {
+            EnvVariable::set_rerun_if_changed_for_all_variants(
+                &mut flags,
+                name,
+                &self.env_suffixes,
+            );
         }
 
         Ok(flags)
     }
-}
 
-#[derive(Debug)]
-/// Error used in return value of `Config::add_build_internal` closures
-pub enum BuildInternalClosureError {
-    /// `pkg-config` error
-    PkgConfig(pkg_config::Error),
-    /// General failure
-    Failed(String),
-}
+    // The cfgs set for the dependencies which have been found, and the declaration of all
+    // the cfgs system-deps may set so rustc doesn't warn about the others.
+    fn gen_cfg_flags(&self) -> BuildFlags {
+        let mut flags = BuildFlags::new();
 
-impl From<pkg_config::Error> for BuildInternalClosureError {
-    fn from(err: pkg_config::Error) -> Self {
-        Self::PkgConfig(err)
+        for cfg in self.cfgs.iter() {
+            flags.add(BuildFlag::RustcCheckCfg(cfg.clone(), self.double_colon));
+        }
+
+        for (name, lib) in self.iter() {
+            flags.add(BuildFlag::RustcCfg(format!(
+                "system_deps_have_{}",
+                name.to_snake_case()
+            )));
+
+            if let Some(alt) = &lib.alternative {
+                flags.add(BuildFlag::RustcCfg(format!(
+                    "system_deps_{}_alt_{}",
+                    name.to_snake_case(),
+                    alt.to_snake_case()
+                )));
+            }
+
+            // The version is unknown if the library has been defined using env variables
+            let thresholds = self.version_cfgs.get(name).map(Vec::as_slice);
+            for (version, cfg) in thresholds.unwrap_or_default() {
+                let reached = VersionReq::parse(&format!(">= {version}"))
+                    .is_ok_and(|req| !lib.version.is_empty() && req.matches(&lib.version));
+                if reached {
+                    flags.add(BuildFlag::RustcCfg(cfg.clone()));
+                }
+            }
+        }
+
+        flags
     }
-}
 
-impl BuildInternalClosureError {
-    /// Create a new `BuildInternalClosureError::Failed` representing a general
-    /// failure.
+    // Export the dependencies found to the build scripts of the crates depending on this one,
+    // see `Dependencies::from_dep_env`.
+    fn gen_metadata_flags(&self) -> BuildFlags {
+        let mut flags = BuildFlags::new();
+
+        let names = self.libs.keys().cloned().collect::<Vec<_>>();
+        flags.add(BuildFlag::Metadata(
+            "SYSTEM_DEPS".to_string(),
+            names.join(" "),
+            self.double_colon,
+        ));
+
+        for (name, lib) in self.iter() {
+            let prefix = format!("SYSTEM_DEPS_{}", envify(name));
+            for (key, value) in lib.to_dep_env() {
+                flags.add(BuildFlag::Metadata(
+                    format!("{prefix}_{key}"),
+                    value,
+                    self.double_colon,
+                ));
+            }
+        }
+
+        flags
+    }
+
+    fn add_cfgs(&mut self, dep: &metadata::Dependency) {
+        let name = dep.key.to_snake_case();
+        self.cfgs.push(format!("system_deps_have_{name}"));
+        for alt in dep.alternatives.iter() {
+            self.cfgs.push(format!(
+                "system_deps_{name}_alt_{}",
+                alt.name.to_snake_case()
+            ));
+        }
+
+        let version_cfgs = dep
+            .version_thresholds()
+            .into_iter()
+            .map(|version| {
+                let suffix = version.replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+                let cfg = format!("system_deps_{name}_version_{suffix}");
+                self.cfgs.push(cfg.clone());
+                (version, cfg)
+            })
+            .collect();
+        self.version_cfgs.insert(dep.key.clone(), version_cfgs);
+    }
+}
+
+// Alternatives have their own version schemes, so there is no version to pass
+// to the build-internal closure
+fn check_build_internal_alternatives(dep: &metadata::Dependency) -> Result<(), Error> {
+    if dep.alternatives.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::BuildInternalInvalid(format!(
+            "{} cannot be built internally as it defines alternatives",
+            dep.key
+        )))
+    }
+}
+
+// The modifiers and the `#[link]` name of `lib`, combining the ones defined for all the
+// libraries of the dependency and for this one. Modifiers requiring static linking are
+// dropped if the library is linked dynamically, as it may be with `prefer-static`.
+fn lib_link_modifiers(
+    link_modifiers: &[metadata::LinkModifiers],
+    lib: &str,
+    statik: bool,
+) -> (Vec<String>, Option<String>) {
+    let mut modifiers: Vec<String> = Vec::new();
+    let mut link_name = None;
+
+    for m in link_modifiers
+        .iter()
+        .filter(|m| m.lib.as_deref().map_or(true, |l| l == lib))
+    {
+        for modifier in m.modifiers.iter() {
+            if statik && metadata::LinkModifiers::is_dynamic_only(modifier)
+                || !statik && metadata::LinkModifiers::is_static_only(modifier)
+            {
+                continue;
+            }
+            // The last value of a modifier wins
+            modifiers.retain(|other| other[1..] != modifier[1..]);
+            modifiers.push(modifier.clone());
+        }
+        if m.link_name.is_some() {
+            link_name.clone_from(&m.link_name);
+        }
+    }
+
+    (modifiers, link_name)
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+/// Error used in return value of `Config::add_build_internal` closures and [`Probe::probe`]
+pub enum BuildInternalClosureError {
+    /// `pkg-config` error
+    PkgConfig(pkg_config::Error),
+    /// General failure
+    Failed(String),
+    /// Error raised by [`Library::from_internal_pkg_config`]
+    Error(Box<Error>),
+}
+
+impl From<pkg_config::Error> for BuildInternalClosureError {
+    fn from(err: pkg_config::Error) -> Self {
+        Self::PkgConfig(err)
+    }
+}
+
+impl From<Error> for BuildInternalClosureError {
+    fn from(err: Error) -> Self {
+        Self::Error(Box::new(err))
+    }
+}
+
+impl BuildInternalClosureError {
+    /// Create a new `BuildInternalClosureError::Failed` representing a general
+    /// failure.
     ///
     /// # Arguments
     ///
@@ -531,6 +1400,7 @@ impl std::error::Error for BuildInternalClosureError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Self::PkgConfig(e) => Some(e),
+            Self::Error(e) => Some(e.as_ref()),
             _ => None,
         }
     }
@@ -541,10 +1411,50 @@ impl fmt::Display for BuildInternalClosureError {
         match self {
             Self::PkgConfig(e) => write!(f, "{e}"),
             Self::Failed(s) => write!(f, "{s}"),
+            Self::Error(e) => write!(f, "{e}"),
         }
     }
 }
 
+/// A dependency which has to be found by a [`Probe`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct DependencyRequest<'a> {
+    /// The dependency as defined in `Cargo.toml`, with the `cfg()` overrides applied.
+    pub dependency: &'a metadata::Dependency,
+    /// The name of the library, taking the enabled version features into account.
+    pub name: &'a str,
+    /// Library names to try if [`DependencyRequest::name`] is not found.
+    pub fallback_names: &'a [String],
+    /// The version requirement, taking the enabled version features into account.
+    pub version: &'a str,
+    /// Whether the library should be linked statically.
+    pub statik: bool,
+}
+
+impl DependencyRequest<'_> {
+    /// Check if `version` satisfies the version requirement of the dependency.
+    pub fn version_matches(&self, version: &str) -> bool {
+        VersionReq::parse(self.version)
+            .expect("version checked when parsing")
+            .matches(version)
+    }
+}
+
+/// A custom backend finding system libraries, registered using [`Config::add_probe`].
+///
+/// It is used for dependencies listing `custom:$NAME` in their `sources`.
+pub trait Probe {
+    /// Look for the library requested by `dep`.
+    ///
+    /// Returns `Ok(None)` if the library has not been found, so the next source is tried.
+    fn probe(&self, dep: &DependencyRequest) -> std::result::Result<Option<Library>, ProbeError>;
+}
+
+/// Error used in return value of [`Probe::probe`], the same as the one of the
+/// `Config::add_build_internal` closures.
+pub type ProbeError = BuildInternalClosureError;
+
 // Enum representing the environment variables user can define to tune system-deps.
 #[derive(Debug, PartialEq)]
 enum EnvVariable {
@@ -556,6 +1466,7 @@ enum EnvVariable {
     NoPkgConfig(String),
     BuildInternal(Option<String>),
     Link(Option<String>),
+    LinkModifiers(String),
     LinkerArgs(String),
 }
 
@@ -596,6 +1507,10 @@ impl EnvVariable {
         Self::Link(lib.map(|l| l.to_string()))
     }
 
+    fn new_link_modifiers(lib: &str) -> Self {
+        Self::LinkModifiers(lib.to_string())
+    }
+
     const fn suffix(&self) -> &'static str {
         match self {
             EnvVariable::Lib(_) => "LIB",
@@ -606,24 +1521,43 @@ impl EnvVariable {
             EnvVariable::NoPkgConfig(_) => "NO_PKG_CONFIG",
             EnvVariable::BuildInternal(_) => "BUILD_INTERNAL",
             EnvVariable::Link(_) => "LINK",
+            EnvVariable::LinkModifiers(_) => "LINK_MODIFIERS",
             EnvVariable::LinkerArgs(_) => "LDFLAGS",
         }
     }
 
-    fn set_rerun_if_changed_for_all_variants(flags: &mut BuildFlags, name: &str) {
-        #[inline]
-        fn add_to_flags(flags: &mut BuildFlags, var: EnvVariable) {
-            flags.add(BuildFlag::RerunIfEnvChanged(var));
+    /// The names of the variable, from the most specific one: `$VAR_<target>`, `$VAR_TARGET`
+    /// or `$VAR_HOST` using `suffixes`, then `$VAR`.
+    fn names(&self, suffixes: &[String]) -> Vec<String> {
+        let var = self.to_string();
+        suffixes
+            .iter()
+            .map(|suffix| format!("{var}_{suffix}"))
+            .chain(std::iter::once(var.clone()))
+            .collect()
+    }
+
+    fn add_rerun_if_changed(self, flags: &mut BuildFlags, suffixes: &[String]) {
+        for name in self.names(suffixes) {
+            flags.add(BuildFlag::RerunIfEnvChanged(name));
         }
-        add_to_flags(flags, EnvVariable::new_lib(name));
-        add_to_flags(flags, EnvVariable::new_lib_framework(name));
-        add_to_flags(flags, EnvVariable::new_search_native(name));
-        add_to_flags(flags, EnvVariable::new_search_framework(name));
-        add_to_flags(flags, EnvVariable::new_include(name));
-        add_to_flags(flags, EnvVariable::new_linker_args(name));
-        add_to_flags(flags, EnvVariable::new_no_pkg_config(name));
-        add_to_flags(flags, EnvVariable::new_build_internal(Some(name)));
-        add_to_flags(flags, EnvVariable::new_link(Some(name)));
+    }
+
+    fn set_rerun_if_changed_for_all_variants(
+        flags: &mut BuildFlags,
+        name: &str,
+        suffixes: &[String],
+    ) {
+        EnvVariable::new_lib(name).add_rerun_if_changed(flags, suffixes);
+        EnvVariable::new_lib_framework(name).add_rerun_if_changed(flags, suffixes);
+        EnvVariable::new_search_native(name).add_rerun_if_changed(flags, suffixes);
+        EnvVariable::new_search_framework(name).add_rerun_if_changed(flags, suffixes);
+        EnvVariable::new_include(name).add_rerun_if_changed(flags, suffixes);
+        EnvVariable::new_linker_args(name).add_rerun_if_changed(flags, suffixes);
+        EnvVariable::new_no_pkg_config(name).add_rerun_if_changed(flags, suffixes);
+        EnvVariable::new_build_internal(Some(name)).add_rerun_if_changed(flags, suffixes);
+        EnvVariable::new_link(Some(name)).add_rerun_if_changed(flags, suffixes);
+        EnvVariable::new_link_modifiers(name).add_rerun_if_changed(flags, suffixes);
     }
 }
 
@@ -638,7 +1572,8 @@ impl fmt::Display for EnvVariable {
             | EnvVariable::LinkerArgs(lib)
             | EnvVariable::NoPkgConfig(lib)
             | EnvVariable::BuildInternal(Some(lib))
-            | EnvVariable::Link(Some(lib)) => {
+            | EnvVariable::Link(Some(lib))
+            | EnvVariable::LinkModifiers(lib) => {
                 format!("{}_{}", lib.to_shouty_snake_case(), self.suffix())
             }
             EnvVariable::BuildInternal(None) | EnvVariable::Link(None) => self.suffix().to_string(),
@@ -647,6 +1582,13 @@ impl fmt::Display for EnvVariable {
     }
 }
 
+thread_local! {
+    // The environment and `pkg-config` backend of the `Config` running a build-internal
+    // closure, used by `Library::from_internal_pkg_config`
+    static BUILD_INTERNAL_SETTINGS: RefCell<Option<(EnvVariables, PkgConfigBackend)>> =
+        const { RefCell::new(None) };
+}
+
 type FnBuildInternal =
     dyn FnOnce(&str, &str) -> std::result::Result<Library, BuildInternalClosureError>;
 
@@ -654,6 +1596,10 @@ type FnBuildInternal =
 pub struct Config {
     env: EnvVariables,
     build_internals: HashMap<String, Box<FnBuildInternal>>,
+    probes: HashMap<String, Box<dyn Probe>>,
+    pkg_config_backend: PkgConfigBackend,
+    // Problems met while probing which did not prevent finding the dependencies
+    warnings: Vec<String>,
 }
 
 impl Default for Config {
@@ -672,6 +1618,9 @@ impl Config {
         Self {
             env,
             build_internals: HashMap::new(),
+            probes: HashMap::new(),
+            pkg_config_backend: PkgConfigBackend::default(),
+            warnings: Vec::new(),
         }
     }
 
@@ -686,8 +1635,11 @@ impl Config {
         // Output cargo flags
         println!("{flags}");
 
-        for (name, _) in libraries.iter() {
-            println!("cargo:rustc-cfg=system_deps_have_{}", name.to_snake_case());
+        println!("{}", libraries.gen_cfg_flags());
+
+        // Metadata is only passed to the dependent crates if `links` is defined
+        if libraries.links {
+            println!("{}", libraries.gen_metadata_flags());
         }
 
         Ok(libraries)
@@ -713,8 +1665,35 @@ impl Config {
         build_internals.insert(name.to_string(), Box::new(func));
 
         Self {
-            env: self.env,
             build_internals,
+            ..self
+        }
+    }
+
+    /// Register a custom backend used to find the dependencies listing `custom:$NAME`
+    /// in their `sources`.
+    ///
+    /// # Arguments
+    /// * `name`: the name of the probe, as used in `Cargo.toml`
+    /// * `probe`: the backend
+    pub fn add_probe<P>(self, name: &str, probe: P) -> Self
+    where
+        P: 'static + Probe,
+    {
+        let mut probes = self.probes;
+        probes.insert(name.to_string(), Box::new(probe));
+
+        Self { probes, ..self }
+    }
+
+    /// Select the backend used to resolve `pkg-config` packages.
+    ///
+    /// The `SYSTEM_DEPS_PKG_CONFIG_BACKEND` environment variable, if defined,
+    /// takes precedence over this setting.
+    pub fn pkg_config_backend(self, backend: PkgConfigBackend) -> Self {
+        Self {
+            pkg_config_backend: backend,
+            ..self
         }
     }
 
@@ -729,7 +1708,7 @@ impl Config {
         let dir = self
             .env
             .get("CARGO_MANIFEST_DIR")
-            .ok_or_else(|| Error::InvalidMetadata("$CARGO_MANIFEST_DIR not set".into()))?;
+            .ok_or_else(|| Error::InvalidMetadata("$CARGO_MANIFEST_DIR not set".into(), None))?;
         let mut path = PathBuf::from(dir);
         path.push("Cargo.toml");
 
@@ -737,9 +1716,27 @@ impl Config {
 
         let metadata = MetaData::from_file(&path)?;
 
-        let mut libraries = Dependencies::default();
+        if let Some(ws_path) = &metadata.workspace_manifest {
+            println!("cargo:rerun-if-changed={}", &ws_path.to_string_lossy());
+        }
+
+        let pkg_config_backend = self.get_pkg_config_backend()?;
+        let vcpkg = vcpkg::Vcpkg::from_env(&self.env);
+
+        let mut libraries = Dependencies {
+            env_suffixes: cross::var_suffixes(&self.env),
+            env_vars: self.tracked_env_vars(),
+            double_colon: self.supports_double_colon(),
+            links: self.env.contains("CARGO_MANIFEST_LINKS"),
+            ..Default::default()
+        };
 
         for dep in metadata.deps.iter() {
+            // Declare the cfgs and track the overrides of all the dependencies, including the
+            // ones which are skipped
+            libraries.add_cfgs(dep);
+            libraries.keys.push(dep.key.clone());
+
             if let Some(cfg) = &dep.cfg {
                 // Check if `cfg()` expression matches the target settings
                 if !self.check_cfg(cfg)? {
@@ -747,6 +1744,8 @@ impl Config {
                 }
             }
 
+            let dep = &self.resolve_cfg_overrides(dep)?;
+
             let mut enabled_feature_overrides = Vec::new();
 
             for o in dep.version_overrides.iter() {
@@ -770,24 +1769,20 @@ impl Config {
                 version = dep.version.as_deref();
                 lib_name = dep.lib_name();
                 fallback_lib_names = dep.fallback_names.as_deref().unwrap_or(&[]);
-                optional = dep.optional;
+                optional = dep.is_optional();
             } else {
                 enabled_feature_overrides.sort_by(|a, b| {
-                    fn min_version(r: metadata::VersionRange<'_>) -> &str {
-                        match r.start_bound() {
-                            std::ops::Bound::Unbounded => unreachable!(),
-                            std::ops::Bound::Excluded(_) => unreachable!(),
-                            std::ops::Bound::Included(b) => b,
-                        }
+                    let a = VersionReq::parse(&a.version).expect("version checked when parsing");
+                    let b = VersionReq::parse(&b.version).expect("version checked when parsing");
+
+                    match (a.min_version(), b.min_version()) {
+                        (Some(a), Some(b)) => version_compare::compare(a, b)
+                            .expect("failed to compare versions")
+                            .ord()
+                            .expect("invalid version"),
+                        // Requirements without lower bound are considered the lowest ones
+                        (a, b) => a.is_some().cmp(&b.is_some()),
                     }
-
-                    let a = min_version(metadata::parse_version(&a.version));
-                    let b = min_version(metadata::parse_version(&b.version));
-
-                    version_compare::compare(a, b)
-                        .expect("failed to compare versions")
-                        .ord()
-                        .expect("invalid version")
                 });
                 let highest = enabled_feature_overrides.into_iter().next_back().unwrap();
 
@@ -798,74 +1793,735 @@ impl Config {
                     .as_deref()
                     .or(dep.fallback_names.as_deref())
                     .unwrap_or(&[]);
-                optional = highest.optional.unwrap_or(dep.optional);
+                optional = highest.optional.unwrap_or(dep.is_optional());
             };
 
+            let version = version.or_else(|| dep.alternatives.first().map(|a| a.version.as_str()));
+
+            if let Some(inherit) = &dep.inherit {
+                match self.inherit_library(dep, inherit, version) {
+                    Ok(Some(library)) => libraries.add(&dep.key, library),
+                    // Missing upstream metadata or an unsuitable version means not found
+                    Ok(None) | Err(Error::DepEnv(_)) | Err(Error::UnsatisfiedVersion(..))
+                        if optional => {}
+                    Ok(None) => {
+                        return Err(Error::DepEnv(format!(
+                            "{} has not been found by the crate with `links = \"{}\"`",
+                            dep.key, inherit.links
+                        )))
+                    }
+                    Err(e) => return Err(e),
+                }
+                continue;
+            }
+
             let version = version.ok_or_else(|| {
-                Error::InvalidMetadata(format!("No version defined for {}", dep.key))
+                Error::InvalidMetadata(format!("No version defined for {}", dep.key), None)
             })?;
+            let version_req = VersionReq::parse(version).expect("version checked when parsing");
 
             let name = &dep.key;
-            let build_internal = self.get_build_internal_status(name)?;
+            let build_internal = self.get_build_internal_status(name, dep.build_internal)?;
 
             // should the lib be statically linked?
-            let statik = self
-                .env
-                .has_value(&EnvVariable::new_link(Some(name)), "static")
-                || self.env.has_value(&EnvVariable::new_link(None), "static");
+            let link = self.get_link_kind(name, dep.link)?;
+            let statik = link != LinkKind::Dynamic;
+            let link_modifiers = self.get_link_modifiers(name, &dep.link_modifiers)?;
+
+            let request = DependencyRequest {
+                dependency: dep,
+                name: lib_name,
+                fallback_names: fallback_lib_names,
+                version,
+                statik,
+            };
 
             let mut library = if self.env.contains(&EnvVariable::new_no_pkg_config(name)) {
-                Library::from_env_variables(name)
+                match self.probe_env(dep, &version_req) {
+                    Err(Error::UnsatisfiedVersion(..) | Error::VersionHeader(..)) if optional => {
+                        continue
+                    }
+                    library => library?,
+                }
             } else if build_internal == BuildInternal::Always {
+                check_build_internal_alternatives(dep)?;
                 self.call_build_internal(lib_name, version)?
             } else {
-                let mut config = pkg_config::Config::new();
-                config
-                    .print_system_libs(false)
-                    .cargo_metadata(false)
-                    .range_version(metadata::parse_version(version))
-                    .statik(statik);
-
-                match Self::probe_with_fallback(config, lib_name, fallback_lib_names) {
-                    Ok((lib_name, lib)) => Library::from_pkg_config(lib_name, lib),
+                let probed = self.probe_request(&request, &version_req, pkg_config_backend, &vcpkg);
+
+                match probed {
+                    Ok(library) if link == LinkKind::PreferStatic && !library.is_static() => {
+                        // Probe again without `--static`, so the private libraries are not linked
+                        let request = DependencyRequest {
+                            statik: false,
+                            ..request
+                        };
+                        self.probe_request(&request, &version_req, pkg_config_backend, &vcpkg)?
+                    }
+                    Ok(library) => library,
                     Err(e) => {
                         if build_internal == BuildInternal::Auto {
                             // Try building the lib internally as a fallback
+                            check_build_internal_alternatives(dep)?;
                             self.call_build_internal(name, version)?
                         } else if optional {
                             // If the dep is optional just skip it
                             continue;
                         } else {
-                            return Err(e.into());
+                            return Err(e);
                         }
                     }
                 }
             };
 
-            library.statik = statik;
+            // Track the `.pc` files, as located by the backend which has read them
+            if library.source == Source::PkgConfig {
+                let files = match pkg_config_backend {
+                    PkgConfigBackend::Binary => pc::Command::new(&self.env).files(&library.name),
+                    PkgConfigBackend::Builtin => pc::Resolver::new(&self.env).files(&library.name),
+                };
+                library.pc_file = files.first().cloned();
+                for file in files {
+                    if !libraries.pc_files.contains(&file) {
+                        libraries.pc_files.push(file);
+                    }
+                }
+            }
+
+            library.statik = match link {
+                LinkKind::PreferStatic => library.is_static(),
+                // Static vcpkg triplets and some CMake packages only provide static libraries
+                _ if matches!(library.source, Source::Vcpkg | Source::CMake) => {
+                    statik || library.statik
+                }
+                _ => statik,
+            };
 
             libraries.add(name, library);
+            libraries
+                .link_modifiers
+                .insert(name.clone(), link_modifiers);
         }
+        libraries.warnings = std::mem::take(&mut self.warnings);
         Ok(libraries)
     }
 
+    // Reuse the library found by the build script of the `-sys` crate defining `links`,
+    // instead of probing for it again. `None` if it has not been found by that crate.
+    fn inherit_library(
+        &self,
+        dep: &metadata::Dependency,
+        inherit: &metadata::Inherit,
+        version: Option<&str>,
+    ) -> Result<Option<Library>, Error> {
+        let mut upstream = Dependencies::from_dep_env_with(&self.env, &inherit.links)?;
+        let name = inherit.dependency.as_deref().unwrap_or(&dep.key);
+        let Some(mut library) = upstream.libs.remove(name) else {
+            return Ok(None);
+        };
+
+        if let Some(version) = version {
+            let version_req = VersionReq::parse(version).expect("version checked when parsing");
+            if !version_req.matches(&library.version) {
+                return Err(Error::UnsatisfiedVersion(
+                    dep.key.clone(),
+                    library.version,
+                    version.to_string(),
+                ));
+            }
+        }
+
+        library.source = Source::Inherited;
+        Ok(Some(library))
+    }
+
+    // Use the flags defined in the `SYSTEM_DEPS_$NAME_*` environment variables
+    fn probe_env(
+        &self,
+        dep: &metadata::Dependency,
+        version: &VersionReq,
+    ) -> Result<Library, Error> {
+        let name = &dep.key;
+        let mut library = Library::from_env_variables(name);
+
+        if let Some(header) = &dep.version_header {
+            // Include paths are overridden later on, so look for the header in the directories
+            // of the compiler, as when searching for the library
+            let searcher = search::Searcher::new(&self.env, name);
+            library.version = header::read_version(header, searcher.include_dirs())
+                .map_err(|e| Error::VersionHeader(name.clone(), e))?;
+
+            if !version.matches(&library.version) {
+                return Err(Error::UnsatisfiedVersion(
+                    name.clone(),
+                    library.version,
+                    version.to_string(),
+                ));
+            }
+        }
+
+        Ok(library)
+    }
+
+    // Look for the library using its `sources` if defined, or on the system otherwise
+    fn probe_request(
+        &mut self,
+        request: &DependencyRequest,
+        version_req: &VersionReq,
+        pkg_config_backend: PkgConfigBackend,
+        vcpkg: &Option<vcpkg::Vcpkg>,
+    ) -> Result<Library, Error> {
+        if request.dependency.sources.is_empty() {
+            self.probe_system(request, version_req, pkg_config_backend, vcpkg)
+        } else {
+            self.probe_sources(request, version_req, pkg_config_backend, vcpkg)
+        }
+    }
+
+    // Look for the library on the system, using pkg-config or the backend defined in the metadata
+    fn probe_system(
+        &mut self,
+        request: &DependencyRequest,
+        version_req: &VersionReq,
+        pkg_config_backend: PkgConfigBackend,
+        vcpkg: &Option<vcpkg::Vcpkg>,
+    ) -> Result<Library, Error> {
+        let dep = request.dependency;
+        let lib_name = request.name;
+        let fallback_lib_names = request.fallback_names;
+        let statik = request.statik;
+
+        let sysroot = cross::sysroot(&self.env);
+        let mut prober = match pkg_config_backend {
+            // The `pkg-config` crate only reads the settings of the target from the process
+            // environment, so run the binary directly when cross-compiling.
+            PkgConfigBackend::Binary if cross::is_cross(&self.env) => {
+                let mut command = pc::Command::new(&self.env);
+                command.statik(statik);
+                PkgConfigProber::Command(command)
+            }
+            PkgConfigBackend::Binary => {
+                let mut config = pkg_config::Config::new();
+                config
+                    .print_system_libs(false)
+                    .cargo_metadata(false)
+                    .statik(statik);
+                PkgConfigProber::Binary(config)
+            }
+            PkgConfigBackend::Builtin => {
+                let mut resolver = pc::Resolver::new(&self.env);
+                resolver.statik(statik);
+                PkgConfigProber::Builtin(resolver)
+            }
+        };
+        let any_version = prober.clone();
+        prober.range_version(version_req);
+
+        let probed = if let Some(tool) = &dep.config_tool {
+            self.probe_config_tool(dep, lib_name, tool, version_req)
+        } else if let Some(package) = &dep.cmake {
+            self.probe_cmake(dep, lib_name, package, version_req)
+        } else if let Some(library) = vcpkg.as_ref().and_then(|vcpkg| {
+            // Fall back to pkg-config if the library is not installed by vcpkg
+            match self.probe_vcpkg(
+                vcpkg,
+                dep,
+                lib_name,
+                fallback_lib_names,
+                statik,
+                version_req,
+            ) {
+                Ok(library) => Some(library),
+                Err(Error::SearchFailed(..)) => None,
+                Err(e) => {
+                    self.warnings
+                        .push(format!("Ignoring {} installed by vcpkg: {e}", dep.key));
+                    None
+                }
+            }
+        }) {
+            Ok(library)
+        } else if let Err(e) = self.check_cross_sysroot() {
+            Err(e)
+        } else if dep.alternatives.is_empty() {
+            Self::probe_with_fallback(&prober, lib_name, fallback_lib_names, version_req)
+                .map(|(lib_name, lib)| Library::from_pkg_config(lib_name, lib, sysroot.as_deref()))
+        } else {
+            Self::probe_alternatives(&prober, &dep.alternatives, sysroot.as_deref())
+        };
+        let probed = match (probed, &dep.search) {
+            // Look for the library files directly if pkg-config did not find the library. If it
+            // found a version which is too old, the files can only be used if their version
+            // can be checked.
+            (Err(e), Some(search))
+                if dep.version_header.is_some()
+                    || !Self::is_version_error(&e, &any_version, request) =>
+            {
+                self.search_library(dep, lib_name, search, version_req)
+            }
+            (probed, _) => probed,
+        };
+
+        let library = probed?;
+        self.check_library(dep, &library)?;
+        Ok(library)
+    }
+
+    // Whether probing failed because the library has been found in a version not satisfying the
+    // requirement. The `pkg-config` binary checks the version itself, so the library is probed
+    // again with `any_version` to tell whether it is missing.
+    fn is_version_error(
+        error: &Error,
+        any_version: &PkgConfigProber,
+        request: &DependencyRequest,
+    ) -> bool {
+        let dep = request.dependency;
+        match error {
+            Error::UnsatisfiedVersion(..) => true,
+            Error::PkgConfig(_) if dep.alternatives.is_empty() => std::iter::once(request.name)
+                .chain(request.fallback_names.iter().map(String::as_str))
+                .any(|name| any_version.probe(name).is_ok()),
+            Error::PkgConfig(_) => dep
+                .alternatives
+                .iter()
+                .any(|alt| any_version.probe(&alt.name).is_ok()),
+            _ => false,
+        }
+    }
+
+    // The environment variables affecting how dependencies are found, other than the
+    // `SYSTEM_DEPS_$NAME_*` overrides
+    fn tracked_env_vars(&self) -> Vec<String> {
+        let mut vars = vec![
+            "SYSTEM_DEPS_PKG_CONFIG_BACKEND".to_string(),
+            "VCPKG_ROOT".to_string(),
+            "VCPKG_INSTALLED_DIR".to_string(),
+            "VCPKG_DEFAULT_TRIPLET".to_string(),
+            "SYSROOT".to_string(),
+            "PKG_CONFIG_ALLOW_SYSTEM_LIBS".to_string(),
+        ];
+        vars.extend(cross::sysroot_var_names(&self.env));
+        for var in [
+            "PKG_CONFIG",
+            "PKG_CONFIG_PATH",
+            "PKG_CONFIG_LIBDIR",
+            "PKG_CONFIG_SYSROOT_DIR",
+            "PKG_CONFIG_ALLOW_CROSS",
+            "PKG_CONFIG_SYSTEM_LIBRARY_PATH",
+        ] {
+            vars.extend(cross::targeted_var_names(&self.env, var));
+        }
+        vars
+    }
+
+    // The `cargo::` syntax is supported since Cargo 1.77, and rejected if the crate
+    // declares an older `rust-version`.
+    fn supports_double_colon(&self) -> bool {
+        match self.env.get("CARGO_PKG_RUST_VERSION") {
+            Some(rust_version) if !rust_version.is_empty() => {
+                let rust_version = rust_version.split('-').next().unwrap_or(&rust_version);
+                VersionReq::parse(">= 1.77").unwrap().matches(rust_version)
+            }
+            _ => true,
+        }
+    }
+
+    // Libraries of the host can't be used when cross-compiling, so a sysroot has to be defined
+    // unless pkg-config has been configured for the target.
+    fn check_cross_sysroot(&self) -> Result<(), Error> {
+        if !cross::is_cross(&self.env) || cross::sysroot(&self.env).is_some() {
+            return Ok(());
+        }
+
+        let configured = match cross::targeted_var(&self.env, "PKG_CONFIG_ALLOW_CROSS") {
+            Some(v) => v != "0",
+            // A `pkg-config` dedicated to the target knows where its libraries are
+            None => cross::pkg_config_exe(&self.env).is_some(),
+        };
+        if configured {
+            Ok(())
+        } else {
+            Err(Error::CrossCompilationNoSysroot(
+                self.env.get("TARGET").unwrap_or_default(),
+            ))
+        }
+    }
+
+    // Try the `sources` of the dependency in order, until one of them finds the library
+    fn probe_sources(
+        &mut self,
+        request: &DependencyRequest,
+        version_req: &VersionReq,
+        pkg_config_backend: PkgConfigBackend,
+        vcpkg: &Option<vcpkg::Vcpkg>,
+    ) -> Result<Library, Error> {
+        let dep = request.dependency;
+        let name = &dep.key;
+        let mut errors = Vec::new();
+
+        for source in dep.sources.iter() {
+            let probed = match source {
+                ProbeSource::Env => {
+                    let defined = self.env.contains(&EnvVariable::new_lib(name))
+                        || self.env.contains(&EnvVariable::new_lib_framework(name));
+                    if defined {
+                        self.probe_env(dep, version_req)
+                    } else {
+                        Err(Error::MissingLib(name.clone()))
+                    }
+                }
+                ProbeSource::PkgConfig => {
+                    self.probe_system(request, version_req, pkg_config_backend, vcpkg)
+                }
+                ProbeSource::Internal => self.call_build_internal(request.name, request.version),
+                ProbeSource::Custom(probe_name) => self.call_probe(probe_name, request),
+            };
+
+            match probed {
+                Ok(library) => return Ok(library),
+                Err(e) => errors.push((source.clone(), e)),
+            }
+        }
+
+        Err(Error::SourcesFailed(name.clone(), errors))
+    }
+
+    fn call_probe(&self, probe_name: &str, request: &DependencyRequest) -> Result<Library, Error> {
+        let name = &request.dependency.key;
+        let probe = self.probes.get(probe_name).ok_or_else(|| {
+            Error::InvalidMetadata(
+                format!("No probe named {probe_name} has been registered to find {name}"),
+                None,
+            )
+        })?;
+
+        let library = probe
+            .probe(request)
+            .map_err(|e| Error::Probe(name.clone(), probe_name.to_string(), e))?
+            .ok_or_else(|| {
+                Error::Probe(
+                    name.clone(),
+                    probe_name.to_string(),
+                    ProbeError::failed("library not found"),
+                )
+            })?;
+
+        if !request.version_matches(&library.version) {
+            return Err(Error::UnsatisfiedVersion(
+                name.clone(),
+                library.version,
+                request.version.to_string(),
+            ));
+        }
+
+        Ok(library)
+    }
+
+    // Apply the `cfg()` overrides of the dependency matching the target
+    fn resolve_cfg_overrides(
+        &self,
+        dep: &metadata::Dependency,
+    ) -> Result<metadata::Dependency, Error> {
+        let mut resolved = dep.clone();
+
+        for o in dep.cfg_overrides.iter() {
+            if self.check_cfg(&o.cfg)? {
+                resolved.apply_cfg_override(o);
+            }
+        }
+
+        Ok(resolved)
+    }
+
     fn probe_with_fallback<'a>(
-        config: pkg_config::Config,
+        prober: &PkgConfigProber,
         name: &'a str,
         fallback_names: &'a [String],
-    ) -> Result<(&'a str, pkg_config::Library), pkg_config::Error> {
-        let error = match config.probe(name) {
-            Ok(x) => return Ok((name, x)),
+        version: &VersionReq,
+    ) -> Result<(&'a str, pc::PcLibrary), Error> {
+        // pkg-config checks the version range but requirements such as `!= 1.2`
+        // can only be checked once the library has been found.
+        let error = match prober.probe(name) {
+            Ok(x) if version.matches(&x.version) => return Ok((name, x)),
+            Ok(x) => Error::UnsatisfiedVersion(name.into(), x.version, version.to_string()),
             Err(e) => e,
         };
         for name in fallback_names {
-            if let Ok(library) = config.probe(name) {
-                return Ok((name, library));
+            match prober.probe(name) {
+                Ok(library) if version.matches(&library.version) => return Ok((name, library)),
+                _ => {}
             }
         }
         Err(error)
     }
 
+    fn probe_config_tool(
+        &self,
+        dep: &metadata::Dependency,
+        lib_name: &str,
+        tool: &metadata::ConfigTool,
+        version: &VersionReq,
+    ) -> Result<Library, Error> {
+        let name = &dep.key;
+        let manifest_dir = self
+            .env
+            .get("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        let lib = config_tool::probe(tool, &manifest_dir, &pc::Resolver::new(&self.env))
+            .map_err(|e| Error::ConfigToolFailed(name.clone(), e))?;
+
+        if tool.version.is_empty() {
+            // A lower bound can't be enforced without knowing the version
+            if let (Some(min), false) = (version.min_version(), tool.allow_unknown_version) {
+                return Err(Error::ConfigToolFailed(
+                    name.clone(),
+                    format!(
+                        "version {min} or later is required but `version` is empty, \
+                        set `allow-unknown-version = true` to accept an unknown version"
+                    ),
+                ));
+            }
+        } else if !version.matches(&lib.version) {
+            return Err(Error::UnsatisfiedVersion(
+                name.clone(),
+                lib.version,
+                version.to_string(),
+            ));
+        }
+
+        let sysroot = cross::sysroot(&self.env);
+        let mut library = Library::from_pkg_config(lib_name, lib, sysroot.as_deref());
+        library.source = Source::ConfigTool;
+        Ok(library)
+    }
+
+    fn probe_cmake(
+        &self,
+        dep: &metadata::Dependency,
+        lib_name: &str,
+        package: &metadata::CMakePackage,
+        version: &VersionReq,
+    ) -> Result<Library, Error> {
+        let name = &dep.key;
+        let cmake = self.env.get("CMAKE").unwrap_or_else(|| "cmake".to_string());
+        let out_dir = self
+            .env
+            .get("OUT_DIR")
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::CMakeFailed(name.clone(), "$OUT_DIR not set".into()))?;
+        let mut probe = cmake::CMakeProbe::new(cmake, &out_dir, name);
+        // Packages of the host can't be used for the target, as the cmake crate does
+        if let Some(toolchain_file) = cross::targeted_var(&self.env, "CMAKE_TOOLCHAIN_FILE") {
+            probe.toolchain_file(toolchain_file);
+        } else if cross::is_cross(&self.env) {
+            return Err(Error::CMakeFailed(
+                name.clone(),
+                "$CMAKE_TOOLCHAIN_FILE has to be defined when cross-compiling".into(),
+            ));
+        }
+        let (lib, archives) = probe
+            .probe(package, &pc::Resolver::new(&self.env))
+            .map_err(|e| Error::CMakeFailed(name.clone(), e))?;
+
+        // Not all packages define their version, so a lower bound can't always be enforced
+        if lib.version.is_empty() {
+            if let (Some(min), false) = (version.min_version(), package.allow_unknown_version) {
+                return Err(Error::CMakeFailed(
+                    name.clone(),
+                    format!(
+                        "version {min} or later is required but {} does not define its version, \
+                        set `allow-unknown-version = true` to accept an unknown version",
+                        package.package
+                    ),
+                ));
+            }
+        } else if !version.matches(&lib.version) {
+            return Err(Error::UnsatisfiedVersion(
+                name.clone(),
+                lib.version,
+                version.to_string(),
+            ));
+        }
+
+        let sysroot = cross::sysroot(&self.env);
+        let mut library = Library::from_pkg_config(lib_name, lib, sysroot.as_deref());
+        library.source = Source::CMake;
+        for lib in library.libs.iter_mut() {
+            if archives.contains(&lib.name) {
+                lib.is_static_available = true;
+            }
+        }
+        // Packages only providing archives can't be linked dynamically
+        library.statik = library.is_static();
+        Ok(library)
+    }
+
+    fn search_library(
+        &self,
+        dep: &metadata::Dependency,
+        lib_name: &str,
+        search: &metadata::Search,
+        version: &VersionReq,
+    ) -> Result<Library, Error> {
+        let mut library = search::Searcher::new(&self.env, &dep.key)
+            .find(lib_name, search)
+            .map_err(|e| Error::SearchFailed(dep.key.clone(), e))?;
+        Self::check_version_header(dep, &mut library, version)?;
+
+        Ok(library)
+    }
+
+    // The version of libraries found by looking for their files can only be
+    // checked if it is defined in a header
+    fn check_version_header(
+        dep: &metadata::Dependency,
+        library: &mut Library,
+        version: &VersionReq,
+    ) -> Result<(), Error> {
+        let name = &dep.key;
+
+        if let Some(header) = &dep.version_header {
+            library.version = header::read_version(header, &library.include_paths)
+                .map_err(|e| Error::VersionHeader(name.clone(), e))?;
+
+            if !version.matches(&library.version) {
+                return Err(Error::UnsatisfiedVersion(
+                    name.clone(),
+                    library.version.clone(),
+                    version.to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn probe_vcpkg(
+        &self,
+        vcpkg: &vcpkg::Vcpkg,
+        dep: &metadata::Dependency,
+        lib_name: &str,
+        fallback_names: &[String],
+        statik: bool,
+        version: &VersionReq,
+    ) -> Result<Library, Error> {
+        let mut resolver = pc::Resolver::new(&self.env);
+        resolver
+            .set_search_paths(vcpkg.pkg_config_dirs())
+            .statik(statik || vcpkg.is_static());
+        let prober = PkgConfigProber::Builtin(resolver);
+
+        let mut library =
+            match Self::probe_with_fallback(&prober, lib_name, fallback_names, version) {
+                // vcpkg trees are not part of the sysroot
+                Ok((lib_name, lib)) => Library::from_pkg_config(lib_name, lib, None),
+                Err(e @ Error::UnsatisfiedVersion(..)) => return Err(e),
+                Err(_) => {
+                    // Not all ports install `.pc` files, look for the library files instead
+                    let search = dep.search.clone().unwrap_or_else(|| metadata::Search {
+                        libs: vec![lib_name.to_string()],
+                        headers: Vec::new(),
+                    });
+                    let include_dir = vcpkg.include_dir();
+                    let mut library = search::Searcher::in_dirs(
+                        &self.env,
+                        vec![vcpkg.lib_dir()],
+                        vec![include_dir.clone()],
+                    )
+                    .find(lib_name, &search)
+                    .map_err(|e| Error::SearchFailed(dep.key.clone(), e))?;
+                    if !library.include_paths.contains(&include_dir) {
+                        library.include_paths.push(include_dir);
+                    }
+                    Self::check_version_header(dep, &mut library, version)?;
+                    library
+                }
+            };
+
+        library.source = Source::Vcpkg;
+        library.statik = vcpkg.is_static();
+        Ok(library)
+    }
+
+    // Check that the headers and symbols required by the dependency are usable
+    fn check_library(&self, dep: &metadata::Dependency, lib: &Library) -> Result<(), Error> {
+        if dep.check_headers.is_empty() && dep.check_symbols.is_empty() {
+            return Ok(());
+        }
+
+        let name = &dep.key;
+        // Use the compiler and flags of the target, as the cc crate does. `$CC` may also
+        // contain arguments, such as a wrapper followed by the compiler.
+        let cc = cross::targeted_var(&self.env, "CC").unwrap_or_default();
+        let mut args = cc.split_whitespace().map(str::to_string);
+        let cc = args.next().unwrap_or_else(|| "cc".to_string());
+        let mut args = args.collect::<Vec<_>>();
+        if let Some(cflags) = cross::targeted_var(&self.env, "CFLAGS") {
+            args.extend(cflags.split_whitespace().map(str::to_string));
+        }
+        if let Some(sysroot) = cross::sysroot(&self.env) {
+            args.push(format!("--sysroot={}", sysroot.display()));
+        }
+        let out_dir = self
+            .env
+            .get("OUT_DIR")
+            .map(PathBuf::from)
+            .ok_or_else(|| Error::CheckFailed(name.clone(), "$OUT_DIR not set".into()))?;
+        let checker = check::Checker::new(cc, args, &out_dir);
+
+        if !dep.check_headers.is_empty() {
+            checker
+                .check_headers(name, lib, &dep.check_headers)
+                .map_err(|e| Error::CheckFailed(name.clone(), e))?;
+        }
+        if !dep.check_symbols.is_empty() {
+            checker
+                .check_symbols(name, lib, &dep.check_symbols)
+                .map_err(|e| Error::CheckFailed(name.clone(), e))?;
+        }
+
+        Ok(())
+    }
+
+    fn probe_alternatives(
+        prober: &PkgConfigProber,
+        alternatives: &[metadata::Alternative],
+        sysroot: Option<&Path>,
+    ) -> Result<Library, Error> {
+        let mut error = None;
+
+        for alt in alternatives {
+            let version = VersionReq::parse(&alt.version).expect("version checked when parsing");
+            let mut prober = prober.clone();
+            prober.range_version(&version);
+
+            match Self::probe_with_fallback(&prober, &alt.name, &[], &version) {
+                Ok((name, lib)) => {
+                    let mut library = Library::from_pkg_config(name, lib, sysroot);
+                    library.alternative = Some(alt.name.clone());
+                    return Ok(library);
+                }
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+
+        Err(error.expect("alternatives cannot be empty"))
+    }
+
+    fn get_pkg_config_backend(&self) -> Result<PkgConfigBackend, Error> {
+        match self.env.get("SYSTEM_DEPS_PKG_CONFIG_BACKEND") {
+            Some(s) => PkgConfigBackend::from_str(&s).map_err(|_| {
+                Error::PkgConfigBackendInvalid(format!(
+                    "Invalid value in SYSTEM_DEPS_PKG_CONFIG_BACKEND: {s} (allowed: 'binary', 'builtin')"
+                ))
+            }),
+            None => Ok(self.pkg_config_backend),
+        }
+    }
+
     fn get_build_internal_env_var(&self, var: EnvVariable) -> Result<Option<BuildInternal>, Error> {
         match self.env.get(&var).as_deref() {
             Some(s) => {
@@ -880,19 +2536,76 @@ impl Config {
         }
     }
 
-    fn get_build_internal_status(&self, name: &str) -> Result<BuildInternal, Error> {
+    fn get_build_internal_status(
+        &self,
+        name: &str,
+        default: Option<BuildInternal>,
+    ) -> Result<BuildInternal, Error> {
         match self.get_build_internal_env_var(EnvVariable::new_build_internal(Some(name)))? {
             Some(b) => Ok(b),
             None => Ok(self
                 .get_build_internal_env_var(EnvVariable::new_build_internal(None))?
+                .or(default)
                 .unwrap_or_default()),
         }
     }
 
+    fn get_link_kind_env_var(&self, var: EnvVariable) -> Result<Option<LinkKind>, Error> {
+        match self.env.get(&var).as_deref() {
+            Some(s) => {
+                let link = LinkKind::from_str(s).map_err(|_| {
+                    Error::LinkKindInvalid(format!(
+                        "Invalid value in {var}: {s} (allowed: 'static', 'dynamic', 'prefer-static')"
+                    ))
+                })?;
+                Ok(Some(link))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn get_link_kind(&self, name: &str, default: Option<LinkKind>) -> Result<LinkKind, Error> {
+        let lib = self.get_link_kind_env_var(EnvVariable::new_link(Some(name)))?;
+        let all = self.get_link_kind_env_var(EnvVariable::new_link(None))?;
+
+        // Either variable can request static linking, then env variables take
+        // precedence over the settings from the metadata
+        if lib == Some(LinkKind::Static) || all == Some(LinkKind::Static) {
+            Ok(LinkKind::Static)
+        } else {
+            Ok(lib.or(all).or(default).unwrap_or_default())
+        }
+    }
+
+    fn get_link_modifiers(
+        &self,
+        name: &str,
+        default: &[metadata::LinkModifiers],
+    ) -> Result<Vec<metadata::LinkModifiers>, Error> {
+        // Env variables take precedence over the settings from the metadata
+        let var = EnvVariable::new_link_modifiers(name);
+        match self.env.get(&var) {
+            Some(v) => split_string(&v)
+                .iter()
+                .map(|s| {
+                    s.parse().map_err(|e| {
+                        Error::LinkModifiersInvalid(format!("Invalid value in {var}: {e}"))
+                    })
+                })
+                .collect(),
+            None => Ok(default.to_vec()),
+        }
+    }
+
     fn call_build_internal(&mut self, name: &str, version_str: &str) -> Result<Library, Error> {
+        let backend = self.get_pkg_config_backend()?;
         let lib = match self.build_internals.remove(name) {
-            Some(f) => f(name, version_str)
-                .map_err(|e| Error::BuildInternalClosureError(name.into(), e))?,
+            Some(f) => {
+                BUILD_INTERNAL_SETTINGS.with(|s| s.replace(Some((self.env.clone(), backend))));
+                let lib = f(name, version_str);
+                BUILD_INTERNAL_SETTINGS.with(|s| s.replace(None));
+                lib.map_err(|e| Error::BuildInternalClosureError(name.into(), e))?
+            }
             None => {
                 return Err(Error::BuildInternalNoClosure(
                     name.into(),
@@ -902,24 +2615,8 @@ impl Config {
         };
 
         // Check that the lib built internally matches the required version
-        let version = metadata::parse_version(version_str);
-        fn min_version(r: metadata::VersionRange<'_>) -> &str {
-            match r.start_bound() {
-                std::ops::Bound::Unbounded => unreachable!(),
-                std::ops::Bound::Excluded(_) => unreachable!(),
-                std::ops::Bound::Included(b) => b,
-            }
-        }
-        fn max_version(r: metadata::VersionRange<'_>) -> Option<&str> {
-            match r.end_bound() {
-                std::ops::Bound::Included(_) => unreachable!(),
-                std::ops::Bound::Unbounded => None,
-                std::ops::Bound::Excluded(b) => Some(*b),
-            }
-        }
-
-        let min = min_version(version.clone());
-        if version_compare::compare(&lib.version, min) == Ok(version_compare::Cmp::Lt) {
+        let version = VersionReq::parse(version_str).expect("version checked when parsing");
+        if !version.matches(&lib.version) {
             return Err(Error::BuildInternalWrongVersion(
                 name.into(),
                 lib.version,
@@ -927,16 +2624,6 @@ impl Config {
             ));
         }
 
-        if let Some(max) = max_version(version) {
-            if version_compare::compare(&lib.version, max) == Ok(version_compare::Cmp::Ge) {
-                return Err(Error::BuildInternalWrongVersion(
-                    name.into(),
-                    lib.version,
-                    version_str.into(),
-                ));
-            }
-        }
-
         Ok(lib)
     }
 
@@ -945,40 +2632,142 @@ impl Config {
         self.env.contains(var)
     }
 
-    fn check_cfg(&self, cfg: &cfg_expr::Expression) -> Result<bool, Error> {
-        use cfg_expr::{targets::get_builtin_target_by_triple, Predicate};
+    fn check_cfg(&self, cfg: &metadata::CfgExpression) -> Result<bool, Error> {
+        use cfg_expr::Predicate;
 
-        let target = self
-            .env
-            .get("TARGET")
-            .expect("no TARGET env variable defined");
+        let res = cfg.expression().eval(|pred| match pred {
+            Predicate::Target(tp) => self.check_target_predicate(tp),
+            Predicate::Feature(feature) => Some(self.has_feature(feature)),
+            Predicate::TargetFeature(feature) => {
+                Some(self.has_cfg_value("target_feature", feature))
+            }
+            Predicate::Test => Some(self.has_cfg("test")),
+            Predicate::DebugAssertions => Some(self.has_cfg("debug_assertions")),
+            Predicate::ProcMacro => Some(self.has_cfg("proc_macro")),
+            Predicate::Flag(flag) => Some(self.has_cfg(flag)),
+            Predicate::KeyValue { key, val } => Some(self.has_cfg_value(key, val)),
+        });
+
+        res.ok_or_else(|| Error::UnsupportedCfg(cfg.as_str().to_string()))
+    }
 
-        let res = if let Some(target) = get_builtin_target_by_triple(&target) {
-            cfg.eval(|pred| match pred {
-                Predicate::Target(tp) => Some(tp.matches(target)),
-                _ => None,
-            })
-        } else {
-            // Attempt to parse the triple, the target is not an official builtin
-            let triple: cfg_expr::target_lexicon::Triple = target.parse().unwrap_or_else(|e| panic!("TARGET {} is not a builtin target, and it could not be parsed as a valid triplet: {}", target, e));
+    // Value of the `CARGO_CFG_*` variable defined by Cargo for the `name` cfg of the target
+    fn cfg_value(&self, name: &str) -> Option<String> {
+        let var: &str = &format!("CARGO_CFG_{}", name.to_uppercase().replace('-', "_"));
+        self.env.get(var)
+    }
 
-            cfg.eval(|pred| match pred {
-                Predicate::Target(tp) => Some(tp.matches(&triple)),
-                _ => None,
-            })
+    fn has_cfg(&self, name: &str) -> bool {
+        self.cfg_value(name).is_some()
+    }
+
+    fn has_cfg_value(&self, name: &str, value: &str) -> bool {
+        // Cargo joins multiple values of the same cfg with commas
+        self.cfg_value(name)
+            .map(|v| v.split(',').any(|v| v == value))
+            .unwrap_or(false)
+    }
+
+    fn check_target_predicate(&self, tp: &cfg_expr::TargetPredicate) -> Option<bool> {
+        use cfg_expr::{targets::Endian, TargetPredicate};
+
+        let from_cargo = match tp {
+            TargetPredicate::Abi(abi) => self.cfg_value("target_abi").map(|v| v == abi.as_str()),
+            TargetPredicate::Arch(arch) => {
+                self.cfg_value("target_arch").map(|v| v == arch.as_str())
+            }
+            TargetPredicate::Endian(endian) => {
+                let endian = match endian {
+                    Endian::big => "big",
+                    Endian::little => "little",
+                };
+                self.cfg_value("target_endian").map(|v| v == endian)
+            }
+            TargetPredicate::Env(env) => self.cfg_value("target_env").map(|v| v == env.as_str()),
+            TargetPredicate::Family(family) => self
+                .cfg_value("target_family")
+                .map(|_| self.has_cfg_value("target_family", family.as_str())),
+            TargetPredicate::HasAtomic(atomic) => self
+                .cfg_value("target_has_atomic")
+                .map(|_| self.has_cfg_value("target_has_atomic", &atomic.to_string())),
+            TargetPredicate::Os(os) => self.cfg_value("target_os").map(|v| v == os.as_str()),
+            TargetPredicate::Panic(panic) => self.cfg_value("panic").map(|v| v == panic.as_str()),
+            TargetPredicate::PointerWidth(width) => self
+                .cfg_value("target_pointer_width")
+                .map(|v| v == width.to_string()),
+            TargetPredicate::Vendor(vendor) => self
+                .cfg_value("target_vendor")
+                .map(|v| v == vendor.as_str()),
         };
 
-        res.ok_or_else(|| Error::UnsupportedCfg(cfg.original().to_string()))
+        // Fallback to the target triple if Cargo did not provide the information
+        from_cargo.or_else(|| self.check_target_triple(tp))
+    }
+
+    fn check_target_triple(&self, tp: &cfg_expr::TargetPredicate) -> Option<bool> {
+        use cfg_expr::targets::get_builtin_target_by_triple;
+
+        let target = self.env.get("TARGET")?;
+
+        if let Some(target) = get_builtin_target_by_triple(&target) {
+            Some(tp.matches(target))
+        } else {
+            // Attempt to parse the triple, the target is not an official builtin
+            let triple: cfg_expr::target_lexicon::Triple = target.parse().ok()?;
+            Some(tp.matches(&triple))
+        }
     }
 }
 
 #[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
 /// From where the library settings have been retrieved
 pub enum Source {
     /// Settings have been retrieved from `pkg-config`
     PkgConfig,
     /// Settings have been defined using user defined environment variables
     EnvVariables,
+    /// Settings have been found by looking for the library files in the library directories
+    Search,
+    /// Settings have been retrieved from a `*-config` tool
+    ConfigTool,
+    /// Settings have been retrieved from a CMake package config file
+    CMake,
+    /// Settings have been retrieved from a vcpkg installed tree
+    Vcpkg,
+    /// Settings have been exported by another `-sys` crate, which also emitted the link flags
+    Inherited,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PkgConfig => write!(f, "pkg-config"),
+            Self::EnvVariables => write!(f, "env"),
+            Self::Search => write!(f, "search"),
+            Self::ConfigTool => write!(f, "config-tool"),
+            Self::CMake => write!(f, "cmake"),
+            Self::Vcpkg => write!(f, "vcpkg"),
+            Self::Inherited => write!(f, "inherited"),
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pkg-config" => Ok(Self::PkgConfig),
+            "env" => Ok(Self::EnvVariables),
+            "search" => Ok(Self::Search),
+            "config-tool" => Ok(Self::ConfigTool),
+            "cmake" => Ok(Self::CMake),
+            "vcpkg" => Ok(Self::Vcpkg),
+            "inherited" => Ok(Self::Inherited),
+            v => Err(ParseError::VariantNotFound(v.to_owned())),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -1000,6 +2789,7 @@ impl InternalLib {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 /// A system dependency
 pub struct Library {
     /// Name of the library
@@ -1024,17 +2814,140 @@ pub struct Library {
     pub version: String,
     /// library is statically linked
     pub statik: bool,
+    /// name of the alternative used to satisfy the dependency, if any
+    pub alternative: Option<String>,
+    /// `.pc` file the library has been read from, if any
+    pub pc_file: Option<PathBuf>,
 }
 
 impl Library {
-    fn from_pkg_config(name: &str, l: pkg_config::Library) -> Self {
+    // Whether static versions of all the libraries are available, for `prefer-static`.
+    // Libraries defined using env variables are only known after probing.
+    fn is_static(&self) -> bool {
+        !self.libs.is_empty() && self.libs.iter().all(|l| l.is_static_available)
+    }
+
+    // The variables exported for the crates depending on this one, read by `Library::from_dep_env`.
+    // Lists use the same format as the `SYSTEM_DEPS_$NAME_*` overrides.
+    fn to_dep_env(&self) -> Vec<(&'static str, String)> {
+        let join_paths = |paths: &[PathBuf]| {
+            env::join_paths(paths)
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let libs = |statik: bool| {
+            join_escaped(
+                self.libs
+                    .iter()
+                    .filter(|l| !statik || l.is_static_available)
+                    .map(|l| l.name.as_str()),
+            )
+        };
+        let mut defines = self
+            .defines
+            .iter()
+            .map(|(k, v)| match v {
+                Some(v) => format!("{k}={v}"),
+                None => k.clone(),
+            })
+            .collect::<Vec<_>>();
+        defines.sort();
+
+        let mut vars = vec![
+            ("NAME", self.name.clone()),
+            ("VERSION", self.version.clone()),
+            ("SOURCE", self.source.to_string()),
+            ("STATIC", self.statik.to_string()),
+            ("INCLUDE", join_paths(&self.include_paths)),
+            ("LINK_PATHS", join_paths(&self.link_paths)),
+            ("FRAMEWORK_PATHS", join_paths(&self.framework_paths)),
+            ("LIBS", libs(false)),
+            ("STATIC_LIBS", libs(true)),
+            (
+                "FRAMEWORKS",
+                join_escaped(self.frameworks.iter().map(String::as_str)),
+            ),
+            ("DEFINES", join_escaped(defines.iter().map(String::as_str))),
+            (
+                "LDFLAGS",
+                self.ld_args
+                    .iter()
+                    .map(|args| {
+                        let args = args.iter().map(|a| escape(a, &[' ', ',']));
+                        args.collect::<Vec<_>>().join(",")
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        ];
+        if let Some(alternative) = &self.alternative {
+            vars.push(("ALTERNATIVE", alternative.clone()));
+        }
+        if let Some(pc_file) = &self.pc_file {
+            vars.push(("PC_FILE", pc_file.to_string_lossy().into_owned()));
+        }
+        vars
+    }
+
+    fn from_dep_env(get: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let name = get("NAME").ok_or("NAME is not defined")?;
+        let source = get("SOURCE")
+            .unwrap_or_default()
+            .parse()
+            .map_err(|e| format!("SOURCE: {e}"))?;
+        let list = |key| {
+            let value = get(key).unwrap_or_default();
+            split_escaped(&value, ' ')
+                .into_iter()
+                .map(unescape)
+                .collect::<Vec<_>>()
+        };
+        let paths = |key| split_paths(&get(key).unwrap_or_default());
+
+        let static_libs = list("STATIC_LIBS");
+        let libs = list("LIBS")
+            .into_iter()
+            .map(|l| {
+                let is_static_available = static_libs.contains(&l);
+                InternalLib::new(l, is_static_available)
+            })
+            .collect();
+        let defines = list("DEFINES")
+            .into_iter()
+            .map(|d| match d.split_once('=') {
+                Some((k, v)) => (k.to_string(), Some(v.to_string())),
+                None => (d, None),
+            })
+            .collect();
+
+        Ok(Self {
+            name,
+            source,
+            libs,
+            link_paths: paths("LINK_PATHS"),
+            frameworks: list("FRAMEWORKS"),
+            framework_paths: paths("FRAMEWORK_PATHS"),
+            include_paths: paths("INCLUDE"),
+            ld_args: split_escaped(&get("LDFLAGS").unwrap_or_default(), ' ')
+                .into_iter()
+                .map(|l| split_escaped(l, ',').into_iter().map(unescape).collect())
+                .collect(),
+            defines,
+            version: get("VERSION").unwrap_or_default(),
+            statik: get("STATIC").as_deref() == Some("true"),
+            alternative: get("ALTERNATIVE"),
+            pc_file: get("PC_FILE").map(PathBuf::from),
+        })
+    }
+
+    fn from_pkg_config(name: &str, l: impl Into<pc::PcLibrary>, sysroot: Option<&Path>) -> Self {
+        let l = l.into();
+
         // taken from: https://github.com/rust-lang/pkg-config-rs/blob/54325785816695df031cef3b26b6a9a203bbc01b/src/lib.rs#L502
         let system_roots = if cfg!(target_os = "macos") {
             vec![PathBuf::from("/Library"), PathBuf::from("/System")]
         } else {
-            let sysroot = env::var_os("PKG_CONFIG_SYSROOT_DIR")
-                .or_else(|| env::var_os("SYSROOT"))
-                .map(PathBuf::from);
+            let sysroot = sysroot.map(PathBuf::from);
 
             if cfg!(target_os = "windows") {
                 if let Some(sysroot) = sysroot {
@@ -1080,6 +2993,8 @@ impl Library {
             defines: l.defines,
             version: l.version,
             statik: false,
+            alternative: None,
+            pc_file: None,
         }
     }
 
@@ -1096,6 +3011,8 @@ impl Library {
             defines: HashMap::new(),
             version: String::new(),
             statik: false,
+            alternative: None,
+            pc_file: None,
         }
     }
 
@@ -1105,6 +3022,9 @@ impl Library {
     ///
     /// This library will be statically linked.
     ///
+    /// The `.pc` file is resolved using the [backend](#pkg-config-backends) and the environment
+    /// of the [`Config`] calling the closure.
+    ///
     /// # Arguments
     ///
     /// * `pkg_config_dir`: the directory where the library `.pc` file is located
@@ -1129,40 +3049,53 @@ impl Library {
     where
         P: AsRef<Path>,
     {
-        // save current PKG_CONFIG_PATH, so we can restore it
-        let old = env::var("PKG_CONFIG_PATH");
-
-        match old {
-            Ok(ref s) => {
-                let mut paths = env::split_paths(s).collect::<Vec<_>>();
-                paths.push(PathBuf::from(pkg_config_dir.as_ref()));
-                let paths = env::join_paths(paths).unwrap();
-                env::set_var("PKG_CONFIG_PATH", paths)
+        let (env, backend) = match BUILD_INTERNAL_SETTINGS.with(|s| s.borrow().clone()) {
+            Some(settings) => settings,
+            // Not called from a closure run by `Config::probe`
+            None => {
+                let config = Config::new();
+                let backend = config.get_pkg_config_backend()?;
+                (config.env, backend)
             }
-            Err(_) => env::set_var("PKG_CONFIG_PATH", pkg_config_dir.as_ref()),
-        }
-
-        let pkg_lib = pkg_config::Config::new()
-            .atleast_version(version)
-            .print_system_libs(false)
-            .cargo_metadata(false)
-            .statik(true)
-            .probe(lib);
-
-        env::set_var("PKG_CONFIG_PATH", old.unwrap_or_else(|_| "".into()));
-
-        match pkg_lib {
-            Ok(pkg_lib) => {
-                let mut lib = Self::from_pkg_config(lib, pkg_lib);
-                lib.statik = true;
-                Ok(lib)
+        };
+        let version_req = VersionReq::parse(version).map_err(|e| {
+            Error::InvalidMetadata(format!("Invalid version requirement of {lib}: {e}"), None)
+        })?;
+
+        let pkg_lib = match backend {
+            PkgConfigBackend::Binary => {
+                let mut command = pc::Command::new(&env);
+                command
+                    .prepend_search_path(pkg_config_dir.as_ref())
+                    .statik(true);
+                command.probe(lib).map_err(Error::PkgConfigCommand)?
             }
-            Err(e) => Err(e.into()),
+            PkgConfigBackend::Builtin => {
+                let mut resolver = pc::Resolver::new(&env);
+                resolver
+                    .prepend_search_path(pkg_config_dir.as_ref())
+                    .statik(true);
+                resolver.probe(lib).map_err(Error::PkgConfigBuiltin)?
+            }
+        };
+
+        if !version_req.matches(&pkg_lib.version) {
+            return Err(Error::UnsatisfiedVersion(
+                lib.to_string(),
+                pkg_lib.version,
+                version.to_string(),
+            )
+            .into());
         }
+
+        let sysroot = cross::sysroot(&env);
+        let mut lib = Self::from_pkg_config(lib, pkg_lib, sysroot.as_deref());
+        lib.statik = true;
+        Ok(lib)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum EnvVariables {
     Environment,
     #[cfg(test)]
@@ -1175,13 +3108,6 @@ trait EnvVariablesExt<T> {
     }
 
     fn get(&self, var: T) -> Option<String>;
-
-    fn has_value(&self, var: T, val: &str) -> bool {
-        match self.get(var) {
-            Some(v) => v == val,
-            None => false,
-        }
-    }
 }
 
 impl EnvVariablesExt<&str> for EnvVariables {
@@ -1196,9 +3122,9 @@ impl EnvVariablesExt<&str> for EnvVariables {
 
 impl EnvVariablesExt<&EnvVariable> for EnvVariables {
     fn get(&self, var: &EnvVariable) -> Option<String> {
-        let s = var.to_string();
-        let var: &str = s.as_ref();
-        self.get(var)
+        var.names(&cross::var_suffixes(self))
+            .iter()
+            .find_map(|name| self.get(name.as_str()))
     }
 }
 
@@ -1207,10 +3133,15 @@ enum BuildFlag {
     Include(String),
     SearchNative(String),
     SearchFramework(String),
-    Lib(String, bool), // true if static
+    Lib(String, bool, Vec<String>, Option<String>), // true if static, modifiers, `#[link]` name
     LibFramework(String),
-    RerunIfEnvChanged(EnvVariable),
+    RerunIfEnvChanged(String),
+    RerunIfChanged(PathBuf),
     LinkArg(Vec<String>),
+    RustcCfg(String),
+    RustcCheckCfg(String, bool),    // true if using the `cargo::` syntax
+    Metadata(String, String, bool), // true if using the `cargo::` syntax
+    Warning(String),
 }
 
 impl fmt::Display for BuildFlag {
@@ -1219,18 +3150,31 @@ impl fmt::Display for BuildFlag {
             BuildFlag::Include(paths) => write!(f, "include={paths}"),
             BuildFlag::SearchNative(lib) => write!(f, "rustc-link-search=native={lib}"),
             BuildFlag::SearchFramework(lib) => write!(f, "rustc-link-search=framework={lib}"),
-            BuildFlag::Lib(lib, statik) => {
-                if *statik {
-                    write!(f, "rustc-link-lib=static={lib}")
-                } else {
-                    write!(f, "rustc-link-lib={lib}")
+            BuildFlag::Lib(lib, statik, modifiers, link_name) => {
+                write!(f, "rustc-link-lib=")?;
+                match (*statik, modifiers.is_empty()) {
+                    (true, true) => write!(f, "static=")?,
+                    (true, false) => write!(f, "static:{}=", modifiers.join(","))?,
+                    (false, true) => {}
+                    (false, false) => write!(f, "dylib:{}=", modifiers.join(","))?,
+                }
+                match link_name {
+                    Some(link_name) => write!(f, "{link_name}:{lib}"),
+                    None => write!(f, "{lib}"),
                 }
             }
             BuildFlag::LibFramework(lib) => write!(f, "rustc-link-lib=framework={lib}"),
             BuildFlag::RerunIfEnvChanged(env) => write!(f, "rerun-if-env-changed={env}"),
+            BuildFlag::RerunIfChanged(path) => write!(f, "rerun-if-changed={}", path.display()),
             BuildFlag::LinkArg(ld_option) => {
                 write!(f, "rustc-link-arg=-Wl,{}", ld_option.join(","))
             }
+            BuildFlag::RustcCfg(cfg) => write!(f, "rustc-cfg={cfg}"),
+            BuildFlag::RustcCheckCfg(cfg, _) => write!(f, "rustc-check-cfg=cfg({cfg})"),
+            BuildFlag::Metadata(key, value, true) => write!(f, "metadata={key}={value}"),
+            BuildFlag::Metadata(key, value, false) => write!(f, "{key}={value}"),
+            // Each line is a separate instruction
+            BuildFlag::Warning(msg) => write!(f, "warning={}", msg.replace('\n', " ")),
         }
     }
 }
@@ -1251,7 +3195,11 @@ impl BuildFlags {
 impl fmt::Display for BuildFlags {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for flag in self.0.iter() {
-            writeln!(f, "cargo:{flag}")?;
+            if let BuildFlag::RustcCheckCfg(_, true) | BuildFlag::Metadata(_, _, true) = flag {
+                writeln!(f, "cargo::{flag}")?;
+            } else {
+                writeln!(f, "cargo:{flag}")?;
+            }
         }
         Ok(())
     }
@@ -1266,6 +3214,11 @@ fn split_paths(value: &str) -> Vec<PathBuf> {
     }
 }
 
+// The mangling used by Cargo for the names of the `DEP_$LINKS_$KEY` variables
+fn envify(s: &str) -> String {
+    s.to_uppercase().replace('-', "_")
+}
+
 fn split_string(value: &str) -> Vec<String> {
     if !value.is_empty() {
         value.split(' ').map(|s| s.to_string()).collect()
@@ -1274,10 +3227,68 @@ fn split_string(value: &str) -> Vec<String> {
     }
 }
 
-#[derive(Debug, PartialEq, Default)]
-enum BuildInternal {
+// Join `items` with spaces, escaping the ones they contain
+fn join_escaped<'a>(items: impl Iterator<Item = &'a str>) -> String {
+    items
+        .map(|i| escape(i, &[' ']))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Escape the `separators` and backslashes of `value` with a backslash
+fn escape(value: &str, separators: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || separators.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Split `value` on the occurrences of `separator` which are not escaped, keeping the escapes
+fn split_escaped(value: &str, separator: char) -> Vec<&str> {
+    if value.is_empty() {
+        return Vec::new();
+    }
+
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut chars = value.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == separator {
+            items.push(&value[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    items.push(&value[start..]);
+    items
+}
+
+// Remove the backslashes escaping the characters of `value`
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// When a dependency should be built internally
+pub enum BuildInternal {
+    /// Build the dependency only if the required version has not been found
     Auto,
+    /// Always build the dependency
     Always,
+    /// Never build the dependency
     #[default]
     Never,
 }
@@ -1295,8 +3306,122 @@ impl FromStr for BuildInternal {
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum ParseError {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// The backend used to resolve `pkg-config` packages
+pub enum PkgConfigBackend {
+    /// Run the `pkg-config` binary
+    #[default]
+    Binary,
+    /// Read the `.pc` files without running any external program
+    Builtin,
+}
+
+impl FromStr for PkgConfigBackend {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "binary" => Ok(Self::Binary),
+            "builtin" => Ok(Self::Builtin),
+            v => Err(ParseError::VariantNotFound(v.to_owned())),
+        }
+    }
+}
+
+// The `pkg-config` backend used to probe a dependency
+#[derive(Debug, Clone)]
+enum PkgConfigProber {
+    Binary(pkg_config::Config),
+    Builtin(pc::Resolver),
+    Command(pc::Command),
+}
+
+impl PkgConfigProber {
+    fn range_version(&mut self, version: &VersionReq) {
+        // The other backends rely on the version being checked after probing
+        if let Self::Binary(config) = self {
+            config.range_version(version.range());
+        }
+    }
+
+    fn probe(&self, name: &str) -> Result<pc::PcLibrary, Error> {
+        match self {
+            Self::Binary(config) => Ok(config.probe(name)?.into()),
+            Self::Builtin(resolver) => resolver.probe(name).map_err(Error::PkgConfigBuiltin),
+            Self::Command(command) => command.probe(name).map_err(Error::PkgConfigCommand),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+/// How a dependency should be linked
+pub enum LinkKind {
+    /// Link dynamically
+    #[default]
+    Dynamic,
+    /// Link statically
+    Static,
+    /// Link statically if static versions of all the libraries are available
+    PreferStatic,
+}
+
+impl FromStr for LinkKind {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dynamic" => Ok(Self::Dynamic),
+            "static" => Ok(Self::Static),
+            "prefer-static" => Ok(Self::PreferStatic),
+            v => Err(ParseError::VariantNotFound(v.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A way to find a dependency, as listed in its `sources`
+pub enum ProbeSource {
+    /// Flags defined using the `SYSTEM_DEPS_$NAME_LIB` environment variables
+    Env,
+    /// `pkg-config`, or the backend defined in the metadata of the dependency
+    PkgConfig,
+    /// The closure registered using `Config::add_build_internal`
+    Internal,
+    /// The probe registered using `Config::add_probe` with this name
+    Custom(String),
+}
+
+impl FromStr for ProbeSource {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "env" => Ok(Self::Env),
+            "pkg-config" => Ok(Self::PkgConfig),
+            "internal" => Ok(Self::Internal),
+            v => match v.strip_prefix("custom:") {
+                Some(name) if !name.is_empty() => Ok(Self::Custom(name.to_owned())),
+                _ => Err(ParseError::VariantNotFound(v.to_owned())),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ProbeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Env => write!(f, "env"),
+            Self::PkgConfig => write!(f, "pkg-config"),
+            Self::Internal => write!(f, "internal"),
+            Self::Custom(name) => write!(f, "custom:{name}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Error returned when parsing a setting value
+pub enum ParseError {
+    /// The value is not one of the allowed variants
     VariantNotFound(String),
 }
 
@@ -1,27 +1,104 @@
-// Parse system-deps metadata from Cargo.toml
-
-use std::{fmt, fs, io::Read, path::Path};
+//! Read the system-deps metadata declared in `Cargo.toml`.
+//!
+//! This module lets external tools, such as packaging tools, inspect the system dependencies
+//! of a crate without probing for them.
+//!
+//! ```
+//! # let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+//! # let path = std::path::Path::new(&manifest_dir).join("src/tests/toml-good/Cargo.toml");
+//! let metadata = system_deps::metadata::parse(&path).unwrap();
+//!
+//! for dep in metadata.deps.iter() {
+//!     println!("{} requires {} {:?}", dep.key, dep.lib_name(), dep.version);
+//! }
+//! ```
+
+use std::{
+    fmt, fs,
+    io::Read,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use toml::{
     de::{DeArray, DeTable, DeValue},
     Spanned,
 };
 
-#[derive(Debug, PartialEq)]
-pub(crate) struct MetaData {
-    pub(crate) deps: Vec<Dependency>,
+use crate::{
+    version::{self, VersionReq},
+    BuildInternal, LinkKind, ProbeSource,
+};
+
+/// Parse the `[package.metadata.system-deps]` section of the `Cargo.toml` file at `path`.
+///
+/// Dependencies inherited using `workspace = true` are resolved using the workspace root manifest.
+pub fn parse<P: AsRef<Path>>(path: P) -> Result<MetaData, crate::Error> {
+    MetaData::from_file(path.as_ref())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+/// The system dependencies declared by a crate.
+pub struct MetaData {
+    /// The declared dependencies.
+    pub deps: Vec<Dependency>,
+    /// The workspace root manifest, if some dependencies have been inherited from it.
+    pub workspace_manifest: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct Dependency {
-    pub(crate) key: String,
-    pub(crate) version: Option<String>,
-    pub(crate) name: Option<String>,
-    pub(crate) fallback_names: Option<Vec<String>>,
-    pub(crate) feature: Option<String>,
-    pub(crate) optional: bool,
-    pub(crate) cfg: Option<cfg_expr::Expression>,
-    pub(crate) version_overrides: Vec<VersionOverride>,
+#[non_exhaustive]
+/// A system dependency declared in `Cargo.toml`.
+pub struct Dependency {
+    /// The `toml` key defining the dependency.
+    pub key: String,
+    /// The version requirement, such as `>= 1.2, < 2.0`.
+    pub version: Option<String>,
+    /// The library name, if different from [`Dependency::key`].
+    pub name: Option<String>,
+    /// Library names to try if [`Dependency::lib_name`] is not found.
+    pub fallback_names: Option<Vec<String>>,
+    /// The cargo feature enabling this dependency.
+    pub feature: Option<String>,
+    /// Whether the dependency is optional, if defined. See [`Dependency::is_optional`].
+    pub optional: Option<bool>,
+    /// The `cfg()` expression the target has to match for this dependency to be used.
+    pub cfg: Option<CfgExpression>,
+    /// Settings overridden when enabling version features such as `v1_2`.
+    pub version_overrides: Vec<VersionOverride>,
+    /// Settings overridden for targets matching a `cfg()` expression.
+    pub cfg_overrides: Vec<CfgOverride>,
+    /// Different libraries which can be used to satisfy this dependency, in order of preference.
+    pub alternatives: Vec<Alternative>,
+    /// How the dependency should be linked, if defined.
+    pub link: Option<LinkKind>,
+    /// Modifiers of the libraries linked for the dependency, such as `+whole-archive`.
+    pub link_modifiers: Vec<LinkModifiers>,
+    /// When the dependency should be built internally, if defined.
+    pub build_internal: Option<BuildInternal>,
+    /// Headers which have to be usable once the library has been found.
+    pub check_headers: Vec<String>,
+    /// Symbols which have to be resolved when linking with the library.
+    pub check_symbols: Vec<String>,
+    /// Header defining the version of the library, if any.
+    pub version_header: Option<VersionHeader>,
+    /// Versions for which a `system_deps_$DEP_version_$VERSION` cfg is set if the library found
+    /// is at least this version.
+    pub version_cfgs: Vec<String>,
+    /// Libraries and headers to look for if the dependency is not found using `pkg-config`.
+    pub search: Option<Search>,
+    /// The `*-config` tool to use instead of `pkg-config`, if any.
+    pub config_tool: Option<ConfigTool>,
+    /// The CMake package to use instead of `pkg-config`, if any.
+    pub cmake: Option<CMakePackage>,
+    /// The ways to find the dependency, in order. Empty if not defined.
+    pub sources: Vec<ProbeSource>,
+    /// The `-sys` crate whose dependency should be reused instead of probing for it, if any.
+    pub inherit: Option<Inherit>,
+    /// Whether the dependency has been inherited from the workspace metadata.
+    pub workspace: bool,
 }
 
 impl Dependency {
@@ -32,7 +109,63 @@ impl Dependency {
         }
     }
 
-    pub(crate) fn lib_name(&self) -> &str {
+    // Fill the dependency using the one defined in the workspace, keeping the member's overrides
+    fn inherit(&mut self, ws_dep: &Dependency) {
+        let member = std::mem::replace(self, ws_dep.clone());
+
+        self.key = member.key;
+        self.version = member.version.or(self.version.take());
+        self.feature = member.feature.or(self.feature.take());
+        self.optional = member.optional.or(self.optional);
+        self.cfg = member.cfg.or(self.cfg.take());
+        self.link = member.link.or(self.link);
+        if !member.link_modifiers.is_empty() {
+            self.link_modifiers = member.link_modifiers;
+        }
+        self.build_internal = member.build_internal.or(self.build_internal);
+        self.workspace = true;
+    }
+
+    /// Whether the dependency is optional, `false` if not defined.
+    pub fn is_optional(&self) -> bool {
+        self.optional.unwrap_or(false)
+    }
+
+    /// Apply the settings of a `cfg()` override matching the target.
+    pub(crate) fn apply_cfg_override(&mut self, o: &CfgOverride) {
+        if let Some(version) = &o.version {
+            self.version = Some(version.clone());
+        }
+        if let Some(name) = &o.name {
+            self.name = Some(name.clone());
+        }
+        if let Some(fallback_names) = &o.fallback_names {
+            self.fallback_names = Some(fallback_names.clone());
+        }
+        if let Some(optional) = o.optional {
+            self.optional = Some(optional);
+        }
+    }
+
+    /// The versions for which a cfg is set: [`Dependency::version_cfgs`] if defined,
+    /// or the lowest versions required by the [`Dependency::version_overrides`].
+    pub fn version_thresholds(&self) -> Vec<String> {
+        if !self.version_cfgs.is_empty() {
+            return self.version_cfgs.clone();
+        }
+
+        self.version_overrides
+            .iter()
+            .filter_map(|o| {
+                let req = VersionReq::parse(&o.version).ok()?;
+                req.min_version().map(|v| v.to_string())
+            })
+            .collect()
+    }
+
+    /// The name of the library to look for: [`Dependency::name`] if defined,
+    /// or [`Dependency::key`] otherwise.
+    pub fn lib_name(&self) -> &str {
         self.name.as_ref().unwrap_or(&self.key)
     }
 }
@@ -45,9 +178,24 @@ impl Default for Dependency {
             name: None,
             fallback_names: None,
             feature: None,
-            optional: false,
+            optional: None,
             cfg: None,
             version_overrides: Vec::new(),
+            cfg_overrides: Vec::new(),
+            alternatives: Vec::new(),
+            link: None,
+            link_modifiers: Vec::new(),
+            build_internal: None,
+            check_headers: Vec::new(),
+            check_symbols: Vec::new(),
+            version_header: None,
+            version_cfgs: Vec::new(),
+            search: None,
+            config_tool: None,
+            cmake: None,
+            sources: Vec::new(),
+            inherit: None,
+            workspace: false,
         }
     }
 }
@@ -78,6 +226,11 @@ enum MetadataError {
     Toml(toml::de::Error),
     UnexpectedVersionSetting(String, String, String),
     UnexpectedKey(String, String, String),
+    UnexpectedWorkspaceKey(String, String),
+    UnexpectedAlternativesKey(String, String),
+    NotInWorkspace(String, String),
+    InvalidVersion(String, version::ParseError),
+    InvalidValue(String, crate::ParseError),
     VersionOverrideBuilder(VersionOverrideBuilderError),
 }
 
@@ -95,6 +248,20 @@ impl fmt::Display for MetadataError {
                 write!(f, "{n}: unexpected version settings key: {k} type: {t}")
             }
             Self::UnexpectedKey(n, k, t) => write!(f, "{n}: unexpected key {k} type {t}"),
+            Self::UnexpectedWorkspaceKey(n, k) => {
+                write!(f, "{n}: key {k} cannot be used with `workspace = true`")
+            }
+            Self::UnexpectedAlternativesKey(n, k) => {
+                write!(f, "{n}: key {k} cannot be used with `alternatives`")
+            }
+            Self::NotInWorkspace(k, w) => {
+                write!(
+                    f,
+                    "`{k}`: not defined in `workspace.metadata.system-deps` of {w}"
+                )
+            }
+            Self::InvalidVersion(k, e) => write!(f, "`{k}`: {e}"),
+            Self::InvalidValue(k, e) => write!(f, "`{k}`: {e}"),
             Self::VersionOverrideBuilder(e) => write!(f, "{e}"),
         }
     }
@@ -105,6 +272,8 @@ impl std::error::Error for MetadataError {
         match self {
             Self::CfgExpr(e) => Some(e),
             Self::Toml(e) => Some(e),
+            Self::InvalidVersion(_, e) => Some(e),
+            Self::InvalidValue(_, e) => Some(e),
             Self::VersionOverrideBuilder(e) => Some(e),
             _ => None,
         }
@@ -129,13 +298,263 @@ impl From<VersionOverrideBuilderError> for MetadataError {
     }
 }
 
+impl MetadataError {
+    fn at(self, span: Range<usize>) -> SpannedError {
+        SpannedError {
+            error: self,
+            span: Some(span),
+        }
+    }
+}
+
+// A `MetadataError` with the location in the manifest of the item causing it, if known.
+#[derive(Debug, PartialEq)]
+struct SpannedError {
+    error: MetadataError,
+    span: Option<Range<usize>>,
+}
+
+impl From<MetadataError> for SpannedError {
+    fn from(error: MetadataError) -> Self {
+        Self { error, span: None }
+    }
+}
+
+impl SpannedError {
+    fn into_error(self, path: &Path, manifest_str: &str) -> crate::Error {
+        let location = self
+            .span
+            .map(|span| crate::MetadataLocation::new(path, manifest_str, span));
+
+        let msg = match &location {
+            Some(l) => format!("{}:{}:{}: {}", path.display(), l.line, l.column, self.error),
+            None => format!("{}: {}", path.display(), self.error),
+        };
+        crate::Error::InvalidMetadata(msg, location)
+    }
+}
+
+impl From<toml::de::Error> for SpannedError {
+    fn from(err: toml::de::Error) -> Self {
+        Self {
+            span: err.span(),
+            error: err.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+/// Dependency settings used when a version feature is enabled.
+pub struct VersionOverride {
+    /// The version feature, such as `v1_2`.
+    pub key: String,
+    /// The version requirement.
+    pub version: String,
+    /// The library name, if overridden.
+    pub name: Option<String>,
+    /// The fallback library names, if overridden.
+    pub fallback_names: Option<Vec<String>>,
+    /// Whether the dependency is optional, if overridden.
+    pub optional: Option<bool>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct VersionOverride {
-    pub(crate) key: String,
-    pub(crate) version: String,
-    pub(crate) name: Option<String>,
-    pub(crate) fallback_names: Option<Vec<String>>,
-    pub(crate) optional: Option<bool>,
+#[non_exhaustive]
+/// A library which can be used to satisfy a dependency.
+pub struct Alternative {
+    /// The library name.
+    pub name: String,
+    /// The version requirement.
+    pub version: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+/// A header defining the version of a library using macros.
+pub struct VersionHeader {
+    /// The header file, relative to the include paths of the library.
+    pub file: String,
+    /// The macro defining the major version.
+    pub major: String,
+    /// The macro defining the minor version.
+    pub minor: String,
+    /// The macro defining the micro version, if any.
+    pub micro: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+/// Libraries and headers to look for in the library and include directories.
+pub struct Search {
+    /// The libraries, such as `foo` for `libfoo.so`.
+    pub libs: Vec<String>,
+    /// The headers, relative to the include directories.
+    pub headers: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+/// A `*-config` tool, such as `llvm-config`, reporting the flags of a library.
+pub struct ConfigTool {
+    /// The tool to run. Paths are relative to the directory of `Cargo.toml`.
+    pub name: String,
+    /// The arguments printing the version, `--version` by default.
+    pub version: Vec<String>,
+    /// The arguments printing the compiler flags, `--cflags` by default.
+    pub cflags: Vec<String>,
+    /// The arguments printing the linker flags, `--libs` by default.
+    pub libs: Vec<String>,
+    /// Whether the library can be used if `version` is empty, so its version is unknown.
+    pub allow_unknown_version: bool,
+}
+
+impl ConfigTool {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: vec!["--version".to_string()],
+            cflags: vec!["--cflags".to_string()],
+            libs: vec!["--libs".to_string()],
+            allow_unknown_version: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+/// Modifiers of the native libraries of a dependency, as passed to `rustc-link-lib`.
+pub struct LinkModifiers {
+    /// The library they apply to, or all the libraries of the dependency if `None`.
+    pub lib: Option<String>,
+    /// The name `#[link]` attributes use to refer to the library, if different
+    /// (the `NAME` of the `NAME:RENAME` form).
+    pub link_name: Option<String>,
+    /// The modifiers, such as `+whole-archive`, `-bundle` or `+verbatim`.
+    pub modifiers: Vec<String>,
+}
+
+impl LinkModifiers {
+    /// Whether the modifier can only be used when linking statically.
+    pub(crate) fn is_static_only(modifier: &str) -> bool {
+        matches!(&modifier[1..], "bundle" | "whole-archive")
+    }
+
+    /// Whether the modifier can only be used when linking dynamically.
+    pub(crate) fn is_dynamic_only(modifier: &str) -> bool {
+        &modifier[1..] == "as-needed"
+    }
+
+    fn with_lib(mut self, lib: &str) -> Self {
+        match lib.split_once(':') {
+            Some((link_name, lib)) => {
+                self.link_name = Some(link_name.to_string());
+                self.lib = Some(lib.to_string());
+            }
+            None => self.lib = Some(lib.to_string()),
+        }
+        self
+    }
+
+    fn parse_modifier(modifier: &str) -> Result<String, crate::ParseError> {
+        let name = modifier
+            .strip_prefix('+')
+            .or_else(|| modifier.strip_prefix('-'));
+        match name {
+            Some("bundle" | "whole-archive" | "as-needed" | "verbatim") => Ok(modifier.to_string()),
+            _ => Err(crate::ParseError::VariantNotFound(modifier.to_owned())),
+        }
+    }
+}
+
+impl FromStr for LinkModifiers {
+    type Err = crate::ParseError;
+
+    /// Parse `[MODIFIERS=]LIB`, with `MODIFIERS` separated by commas and `LIB` optionally
+    /// in the `NAME:RENAME` form, or `MODIFIERS` applying to all the libraries.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (modifiers, lib) = match s.split_once('=') {
+            Some((modifiers, lib)) => (modifiers, Some(lib)),
+            None => (s, None),
+        };
+        let link_modifiers = Self {
+            modifiers: modifiers
+                .split(',')
+                .filter(|m| !m.is_empty())
+                .map(Self::parse_modifier)
+                .collect::<Result<_, _>>()?,
+            ..Default::default()
+        };
+
+        Ok(match lib {
+            Some(lib) => link_modifiers.with_lib(lib),
+            None => link_modifiers,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+/// A dependency found by the build script of another `-sys` crate, exported using its `links` key.
+pub struct Inherit {
+    /// The `links` key of the `-sys` crate.
+    pub links: String,
+    /// The key defining the dependency in the `Cargo.toml` of the `-sys` crate,
+    /// if different from [`Dependency::key`].
+    pub dependency: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+/// A package found using its CMake package config file, such as `FooConfig.cmake`.
+pub struct CMakePackage {
+    /// The package name, as passed to `find_package()`.
+    pub package: String,
+    /// The components of the package which are required.
+    pub components: Vec<String>,
+    /// Whether the library can be used if the package does not define its version.
+    pub allow_unknown_version: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A `cfg()` expression, such as `cfg(target_os = "linux")`.
+pub struct CfgExpression(cfg_expr::Expression);
+
+impl CfgExpression {
+    pub(crate) fn parse(s: &str) -> Result<Self, cfg_expr::ParseError> {
+        cfg_expr::Expression::parse(s).map(Self)
+    }
+
+    /// The expression as written in `Cargo.toml`, without the enclosing `cfg()`.
+    pub fn as_str(&self) -> &str {
+        self.0.original()
+    }
+
+    pub(crate) fn expression(&self) -> &cfg_expr::Expression {
+        &self.0
+    }
+}
+
+impl fmt::Display for CfgExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+/// Dependency settings used when the target matches a `cfg()` expression.
+pub struct CfgOverride {
+    /// The `cfg()` expression the target has to match.
+    pub cfg: CfgExpression,
+    /// The version requirement, if overridden.
+    pub version: Option<String>,
+    /// The library name, if overridden.
+    pub name: Option<String>,
+    /// The fallback library names, if overridden.
+    pub fallback_names: Option<Vec<String>>,
+    /// Whether the dependency is optional, if overridden.
+    pub optional: Option<bool>,
 }
 
 struct VersionOverrideBuilder {
@@ -174,21 +593,40 @@ impl VersionOverrideBuilder {
 
 impl MetaData {
     pub(crate) fn from_file(path: &Path) -> Result<Self, crate::Error> {
-        let mut manifest = fs::File::open(path).map_err(|e| {
-            crate::Error::FailToRead(format!("error opening {}", path.display()), e)
-        })?;
+        let manifest_str = read_manifest(path)?;
+        let mut metadata =
+            Self::from_str(&manifest_str).map_err(|e| e.into_error(path, &manifest_str))?;
+
+        if metadata.deps.iter().any(|dep| dep.workspace) {
+            let ws_path = Self::find_workspace_manifest(path, &manifest_str)?;
+            let ws_str = read_manifest(&ws_path)?;
+
+            let ws_deps = Self::workspace_deps_from_str(&ws_str)
+                .map_err(|e| e.into_error(&ws_path, &ws_str))?;
+
+            for dep in metadata.deps.iter_mut().filter(|dep| dep.workspace) {
+                let ws_dep = ws_deps
+                    .iter()
+                    .find(|ws_dep| ws_dep.key == dep.key)
+                    .ok_or_else(|| {
+                        SpannedError::from(MetadataError::NotInWorkspace(
+                            format!("package.metadata.system-deps.{}", dep.key),
+                            ws_path.display().to_string(),
+                        ))
+                        .into_error(path, &manifest_str)
+                    })?;
+
+                dep.inherit(ws_dep);
+            }
 
-        let mut manifest_str = String::new();
-        manifest.read_to_string(&mut manifest_str).map_err(|e| {
-            crate::Error::FailToRead(format!("error reading {}", path.display()), e)
-        })?;
+            metadata.workspace_manifest = Some(ws_path);
+        }
 
-        Self::from_str(manifest_str)
-            .map_err(|e| crate::Error::InvalidMetadata(format!("{}: {}", path.display(), e)))
+        Ok(metadata)
     }
 
-    fn from_str(manifest_str: String) -> Result<Self, MetadataError> {
-        let toml = DeTable::parse(&manifest_str)?;
+    fn from_str(manifest_str: &str) -> Result<Self, SpannedError> {
+        let toml = DeTable::parse(manifest_str)?;
         let key = "package.metadata.system-deps";
         let meta = toml
             .get_ref()
@@ -199,18 +637,86 @@ impl MetaData {
 
         let deps = Self::parse_deps_table(meta, key, true)?;
 
-        Ok(MetaData { deps })
+        Ok(MetaData {
+            deps,
+            workspace_manifest: None,
+        })
+    }
+
+    fn workspace_deps_from_str(manifest_str: &str) -> Result<Vec<Dependency>, SpannedError> {
+        let toml = DeTable::parse(manifest_str)?;
+        let key = "workspace.metadata.system-deps";
+        let meta = toml
+            .get_ref()
+            .get("workspace")
+            .and_then(|v| v.get_ref().get("metadata"))
+            .and_then(|v| v.get_ref().get("system-deps"))
+            .ok_or_else(|| MetadataError::MissingKey(key.to_owned()))?;
+
+        let deps = Self::parse_deps_table(meta, key, false)?;
+
+        if let Some(dep) = deps.iter().find(|dep| dep.workspace) {
+            return Err(MetadataError::UnexpectedWorkspaceKey(
+                format!("{key}.{}", dep.key),
+                "workspace".to_string(),
+            )
+            .into());
+        }
+
+        Ok(deps)
+    }
+
+    // Find the workspace root manifest the same way Cargo does: using the `package.workspace`
+    // key if defined, otherwise by looking for a manifest with a `[workspace]` table in the
+    // parent directories.
+    fn find_workspace_manifest(path: &Path, manifest_str: &str) -> Result<PathBuf, crate::Error> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let toml = DeTable::parse(manifest_str)
+            .map_err(|e| SpannedError::from(e).into_error(path, manifest_str))?;
+        let explicit_root = toml
+            .get_ref()
+            .get("package")
+            .and_then(|v| v.get_ref().get("workspace"))
+            .and_then(|v| v.get_ref().as_str());
+
+        if let Some(root) = explicit_root {
+            return Ok(dir.join(root).join("Cargo.toml"));
+        }
+
+        for ancestor in dir.ancestors().skip(1) {
+            let candidate = ancestor.join("Cargo.toml");
+            let is_workspace = match fs::read_to_string(&candidate) {
+                Ok(s) => DeTable::parse(&s)
+                    .map(|t| t.get_ref().contains_key("workspace"))
+                    .unwrap_or(false),
+                Err(_) => false,
+            };
+
+            if is_workspace {
+                return Ok(candidate);
+            }
+        }
+
+        Err(crate::Error::InvalidMetadata(
+            format!(
+                "{}: dependencies inherited using `workspace = true` but no workspace root manifest found",
+                path.display()
+            ),
+            None,
+        ))
     }
 
     fn parse_deps_table(
         table: &Spanned<DeValue<'_>>,
         key: &str,
         allow_cfg: bool,
-    ) -> Result<Vec<Dependency>, MetadataError> {
+    ) -> Result<Vec<Dependency>, SpannedError> {
+        let span = table.span();
         let table = table
             .get_ref()
             .as_table()
-            .ok_or_else(|| MetadataError::NotATable(key.to_owned()))?;
+            .ok_or_else(|| MetadataError::NotATable(key.to_owned()).at(span))?;
 
         let mut deps = Vec::new();
 
@@ -218,14 +724,22 @@ impl MetaData {
             let name = name_spanned.as_ref();
             if name.starts_with("cfg(") {
                 if allow_cfg {
-                    let cfg_exp = cfg_expr::Expression::parse(name)?;
+                    let cfg_exp = CfgExpression::parse(name)
+                        .map_err(|e| MetadataError::from(e).at(name_spanned.span()))?;
 
                     for mut dep in Self::parse_deps_table(value, &format!("{key}.{name}"), false)? {
-                        dep.cfg = Some(cfg_exp.clone());
+                        dep.cfg = Some(match dep.cfg {
+                            // The dependency also defines its own `cfg` key
+                            Some(dep_cfg) => all_cfg(&cfg_exp, &dep_cfg)
+                                .map_err(|e| MetadataError::from(e).at(name_spanned.span()))?,
+                            None => cfg_exp.clone(),
+                        });
                         deps.push(dep);
                     }
                 } else {
-                    return Err(MetadataError::NestedCfg(format!("{key}.{name}")));
+                    return Err(
+                        MetadataError::NestedCfg(format!("{key}.{name}")).at(name_spanned.span())
+                    );
                 }
             } else {
                 let dep = Self::parse_dep(key, name, value)?;
@@ -240,19 +754,15 @@ impl MetaData {
         key: &str,
         name: &str,
         value: &Spanned<DeValue<'_>>,
-    ) -> Result<Dependency, MetadataError> {
+    ) -> Result<Dependency, SpannedError> {
         let mut dep = Dependency::new(name);
 
         match value.as_ref() {
             // somelib = "1.0"
             DeValue::String(ref s) => {
-                if !validate_version(s) {
-                    return Err(MetadataError::UnexpectedVersionSetting(
-                        key.into(),
-                        name.into(),
-                        value.as_ref().type_str().to_owned(),
-                    ));
-                }
+                VersionReq::parse(s).map_err(|e| {
+                    MetadataError::InvalidVersion(format!("{key}.{name}"), e).at(value.span())
+                })?;
 
                 dep.version = Some(s.clone().into_owned());
             }
@@ -260,7 +770,9 @@ impl MetaData {
                 Self::parse_dep_table(key, name, &mut dep, t)?;
             }
             _ => {
-                return Err(MetadataError::NotStringOrTable(format!("{key}.{name}")));
+                return Err(
+                    MetadataError::NotStringOrTable(format!("{key}.{name}")).at(value.span())
+                );
             }
         }
 
@@ -272,7 +784,7 @@ impl MetaData {
         name: &str,
         dep: &mut Dependency,
         t: &DeTable<'_>,
-    ) -> Result<(), MetadataError> {
+    ) -> Result<(), SpannedError> {
         for (key_spanned, value) in t {
             let key = key_spanned.as_ref().as_ref();
             match (key, value.as_ref()) {
@@ -280,13 +792,10 @@ impl MetaData {
                     dep.feature = Some(s.clone().into_owned());
                 }
                 ("version", DeValue::String(s)) => {
-                    if !validate_version(s) {
-                        return Err(MetadataError::UnexpectedVersionSetting(
-                            format!("{p_key}.{name}"),
-                            key.into(),
-                            value.as_ref().type_str().to_owned(),
-                        ));
-                    }
+                    VersionReq::parse(s).map_err(|e| {
+                        MetadataError::InvalidVersion(format!("{p_key}.{name}.{key}"), e)
+                            .at(value.span())
+                    })?;
 
                     dep.version = Some(s.clone().into_owned());
                 }
@@ -298,7 +807,108 @@ impl MetaData {
                     dep.fallback_names = Some(Self::parse_name_list(&key, values)?);
                 }
                 ("optional", &DeValue::Boolean(optional)) => {
-                    dep.optional = optional;
+                    dep.optional = Some(optional);
+                }
+                ("link", DeValue::String(s)) => {
+                    let link = s.parse().map_err(|e| {
+                        MetadataError::InvalidValue(format!("{p_key}.{name}.{key}"), e)
+                            .at(value.span())
+                    })?;
+                    dep.link = Some(link);
+                }
+                ("build-internal", DeValue::String(s)) => {
+                    let build_internal = s.parse().map_err(|e| {
+                        MetadataError::InvalidValue(format!("{p_key}.{name}.{key}"), e)
+                            .at(value.span())
+                    })?;
+                    dep.build_internal = Some(build_internal);
+                }
+                ("check-headers", DeValue::Array(values)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.check_headers = Self::parse_name_list(&key, values)?;
+                }
+                ("check-symbols", DeValue::Array(values)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.check_symbols = Self::parse_name_list(&key, values)?;
+                }
+                ("version-header", DeValue::Table(t)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.version_header = Some(Self::parse_version_header(&key, t, value.span())?);
+                }
+                ("alternatives", DeValue::Array(values)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.alternatives = Self::parse_alternatives(&key, values)?;
+                }
+                ("version-cfgs", DeValue::Array(values)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.version_cfgs = Self::parse_name_list(&key, values)?;
+                    // Thresholds are plain versions, not requirements
+                    for (i, (v, value)) in dep.version_cfgs.iter().zip(values.iter()).enumerate() {
+                        VersionReq::parse(&format!(">= {v}")).map_err(|e| {
+                            MetadataError::InvalidVersion(format!("{key}[{i}]"), e).at(value.span())
+                        })?;
+                    }
+                }
+                ("config-tool", DeValue::String(s)) => {
+                    dep.config_tool = Some(ConfigTool::new(s));
+                }
+                ("config-tool", DeValue::Table(t)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.config_tool = Some(Self::parse_config_tool(&key, t, value.span())?);
+                }
+                ("sources", DeValue::Array(values)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.sources = Self::parse_sources(&key, values)?;
+                }
+                ("cmake", DeValue::Table(t)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.cmake = Some(Self::parse_cmake(&key, t, value.span())?);
+                }
+                ("search", DeValue::Table(t)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.search = Some(Self::parse_search(&key, t)?);
+                }
+                ("link-modifiers", DeValue::Array(values)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.link_modifiers = vec![Self::parse_link_modifiers(&key, values)?];
+                }
+                ("link-modifiers", DeValue::Table(t)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.link_modifiers =
+                        t.iter()
+                            .map(|(lib, values)| match values.as_ref() {
+                                DeValue::Array(values) => {
+                                    let key = format!("{key}.{}", lib.as_ref());
+                                    Ok(Self::parse_link_modifiers(&key, values)?
+                                        .with_lib(lib.as_ref()))
+                                }
+                                v => Err(MetadataError::UnexpectedKey(
+                                    key.clone(),
+                                    lib.as_ref().to_string(),
+                                    v.type_str().to_owned(),
+                                )
+                                .at(lib.span())),
+                            })
+                            .collect::<Result<_, _>>()?;
+                }
+                ("inherit", DeValue::Table(t)) => {
+                    let key = format!("{p_key}.{name}.{key}");
+                    dep.inherit = Some(Self::parse_inherit(&key, t, value.span())?);
+                }
+                ("workspace", DeValue::Boolean(true)) => {
+                    dep.workspace = true;
+                }
+                ("cfg", DeValue::String(s)) => {
+                    let cfg = CfgExpression::parse(s)
+                        .map_err(|e| MetadataError::from(e).at(value.span()))?;
+                    dep.cfg = Some(cfg);
+                }
+                (cfg, DeValue::Table(settings)) if cfg.starts_with("cfg(") => {
+                    let cfg_exp = CfgExpression::parse(cfg)
+                        .map_err(|e| MetadataError::from(e).at(key_spanned.span()))?;
+                    let key = format!("{p_key}.{name}.{cfg}");
+                    dep.cfg_overrides
+                        .push(Self::parse_cfg_override(&key, cfg_exp, settings)?);
                 }
                 (version_feature, DeValue::Table(version_settings))
                     if version_feature.starts_with('v') =>
@@ -309,13 +919,13 @@ impl MetaData {
                         let k = k_spanned.as_ref().as_ref();
                         match (k, v.as_ref()) {
                             ("version", DeValue::String(feat_vers)) => {
-                                if !validate_version(feat_vers) {
-                                    return Err(MetadataError::UnexpectedVersionSetting(
-                                        format!("{p_key}.{name}"),
-                                        k.into(),
-                                        v.as_ref().type_str().to_owned(),
-                                    ));
-                                }
+                                VersionReq::parse(feat_vers).map_err(|e| {
+                                    MetadataError::InvalidVersion(
+                                        format!("{p_key}.{name}.{version_feature}.{k}"),
+                                        e,
+                                    )
+                                    .at(v.span())
+                                })?;
 
                                 builder.version = Some(feat_vers.clone().into_owned());
                             }
@@ -334,26 +944,416 @@ impl MetaData {
                                     format!("{p_key}.{name}"),
                                     k.to_owned(),
                                     v.as_ref().type_str().to_owned(),
-                                ));
+                                )
+                                .at(k_spanned.span()));
                             }
                         }
                     }
 
-                    dep.version_overrides.push(builder.build()?);
+                    let o = builder
+                        .build()
+                        .map_err(|e| MetadataError::from(e).at(key_spanned.span()))?;
+                    dep.version_overrides.push(o);
                 }
                 _ => {
                     return Err(MetadataError::UnexpectedKey(
                         format!("{p_key}.{name}"),
                         key.to_owned(),
                         value.as_ref().type_str().to_owned(),
-                    ));
+                    )
+                    .at(key_spanned.span()));
+                }
+            }
+        }
+
+        if dep.workspace {
+            // Only some settings can be overridden when inheriting from the workspace
+            if let Some((key, _)) = t.iter().find(|(k, _)| {
+                !matches!(
+                    k.as_ref().as_ref(),
+                    "workspace"
+                        | "version"
+                        | "feature"
+                        | "optional"
+                        | "cfg"
+                        | "link"
+                        | "build-internal"
+                )
+            }) {
+                return Err(MetadataError::UnexpectedWorkspaceKey(
+                    format!("{p_key}.{name}"),
+                    key.as_ref().to_string(),
+                )
+                .at(key.span()));
+            }
+        }
+
+        if !dep.alternatives.is_empty() {
+            // Alternatives have their own names and version schemes, which overrides cannot
+            // apply to, and no single version to pass to the build-internal closure
+            const NAMING_KEYS: [&str; 3] = ["name", "version", "fallback-names"];
+            if let Some((key, _)) = t.iter().find(|(k, _)| {
+                let k = k.as_ref().as_ref();
+                k == "build-internal"
+                    || NAMING_KEYS.contains(&k)
+                    || dep.version_overrides.iter().any(|o| o.key == k)
+            }) {
+                return Err(MetadataError::UnexpectedAlternativesKey(
+                    format!("{p_key}.{name}"),
+                    key.as_ref().to_string(),
+                )
+                .at(key.span()));
+            }
+
+            for (cfg, value) in t.iter() {
+                let (cfg, DeValue::Table(settings)) = (cfg.as_ref(), value.as_ref()) else {
+                    continue;
+                };
+                if !cfg.starts_with("cfg(") {
+                    continue;
+                }
+                if let Some((key, _)) = settings
+                    .iter()
+                    .find(|(k, _)| NAMING_KEYS.contains(&k.as_ref().as_ref()))
+                {
+                    return Err(MetadataError::UnexpectedAlternativesKey(
+                        format!("{p_key}.{name}.{cfg}"),
+                        key.as_ref().to_string(),
+                    )
+                    .at(key.span()));
                 }
             }
         }
+
         Ok(())
     }
 
-    fn parse_name_list(key: &str, values: &DeArray<'_>) -> Result<Vec<String>, MetadataError> {
+    fn parse_cfg_override(
+        p_key: &str,
+        cfg: CfgExpression,
+        t: &DeTable<'_>,
+    ) -> Result<CfgOverride, SpannedError> {
+        let mut o = CfgOverride {
+            cfg,
+            version: None,
+            name: None,
+            fallback_names: None,
+            optional: None,
+        };
+
+        for (key_spanned, value) in t {
+            let key = key_spanned.as_ref().as_ref();
+            match (key, value.as_ref()) {
+                ("version", DeValue::String(s)) => {
+                    VersionReq::parse(s).map_err(|e| {
+                        MetadataError::InvalidVersion(format!("{p_key}.{key}"), e).at(value.span())
+                    })?;
+
+                    o.version = Some(s.clone().into_owned());
+                }
+                ("name", DeValue::String(s)) => {
+                    o.name = Some(s.clone().into_owned());
+                }
+                ("fallback-names", DeValue::Array(values)) => {
+                    o.fallback_names = Some(Self::parse_name_list(p_key, values)?);
+                }
+                ("optional", &DeValue::Boolean(optional)) => {
+                    o.optional = Some(optional);
+                }
+                (cfg, _) if cfg.starts_with("cfg(") => {
+                    return Err(
+                        MetadataError::NestedCfg(format!("{p_key}.{cfg}")).at(key_spanned.span())
+                    );
+                }
+                _ => {
+                    return Err(MetadataError::UnexpectedKey(
+                        p_key.to_owned(),
+                        key.to_owned(),
+                        value.as_ref().type_str().to_owned(),
+                    )
+                    .at(key_spanned.span()));
+                }
+            }
+        }
+
+        Ok(o)
+    }
+
+    fn parse_version_header(
+        p_key: &str,
+        t: &DeTable<'_>,
+        span: Range<usize>,
+    ) -> Result<VersionHeader, SpannedError> {
+        let mut file = None;
+        let mut major = None;
+        let mut minor = None;
+        let mut micro = None;
+
+        for (k_spanned, v) in t {
+            let k = k_spanned.as_ref().as_ref();
+            let field = match k {
+                "file" => &mut file,
+                "major" => &mut major,
+                "minor" => &mut minor,
+                "micro" => &mut micro,
+                _ => {
+                    return Err(MetadataError::UnexpectedKey(
+                        p_key.to_owned(),
+                        k.to_owned(),
+                        v.as_ref().type_str().to_owned(),
+                    )
+                    .at(k_spanned.span()))
+                }
+            };
+            let s = v
+                .as_ref()
+                .as_str()
+                .ok_or_else(|| MetadataError::NotString(format!("{p_key}.{k}")).at(v.span()))?;
+            *field = Some(s.to_owned());
+        }
+
+        let required = |value: Option<String>, k: &str| {
+            value.ok_or_else(|| MetadataError::MissingKey(format!("{p_key}.{k}")).at(span.clone()))
+        };
+
+        Ok(VersionHeader {
+            file: required(file, "file")?,
+            major: required(major, "major")?,
+            minor: required(minor, "minor")?,
+            micro,
+        })
+    }
+
+    fn parse_search(p_key: &str, t: &DeTable<'_>) -> Result<Search, SpannedError> {
+        let mut search = Search {
+            libs: Vec::new(),
+            headers: Vec::new(),
+        };
+
+        for (k_spanned, v) in t {
+            let k = k_spanned.as_ref().as_ref();
+            match (k, v.as_ref()) {
+                ("libs", DeValue::Array(values)) => {
+                    search.libs = Self::parse_name_list(&format!("{p_key}.{k}"), values)?;
+                }
+                ("headers", DeValue::Array(values)) => {
+                    search.headers = Self::parse_name_list(&format!("{p_key}.{k}"), values)?;
+                }
+                _ => {
+                    return Err(MetadataError::UnexpectedKey(
+                        p_key.to_owned(),
+                        k.to_owned(),
+                        v.as_ref().type_str().to_owned(),
+                    )
+                    .at(k_spanned.span()));
+                }
+            }
+        }
+
+        Ok(search)
+    }
+
+    fn parse_config_tool(
+        p_key: &str,
+        t: &DeTable<'_>,
+        span: Range<usize>,
+    ) -> Result<ConfigTool, SpannedError> {
+        let mut tool = ConfigTool::new("");
+        let mut name = None;
+
+        for (k_spanned, v) in t {
+            let k = k_spanned.as_ref().as_ref();
+            match (k, v.as_ref()) {
+                ("name", DeValue::String(s)) => {
+                    name = Some(s.clone().into_owned());
+                }
+                ("version", DeValue::Array(values)) => {
+                    tool.version = Self::parse_name_list(&format!("{p_key}.{k}"), values)?;
+                }
+                ("cflags", DeValue::Array(values)) => {
+                    tool.cflags = Self::parse_name_list(&format!("{p_key}.{k}"), values)?;
+                }
+                ("libs", DeValue::Array(values)) => {
+                    tool.libs = Self::parse_name_list(&format!("{p_key}.{k}"), values)?;
+                }
+                ("allow-unknown-version", &DeValue::Boolean(allow)) => {
+                    tool.allow_unknown_version = allow;
+                }
+                _ => {
+                    return Err(MetadataError::UnexpectedKey(
+                        p_key.to_owned(),
+                        k.to_owned(),
+                        v.as_ref().type_str().to_owned(),
+                    )
+                    .at(k_spanned.span()));
+                }
+            }
+        }
+
+        tool.name =
+            name.ok_or_else(|| MetadataError::MissingKey(format!("{p_key}.name")).at(span))?;
+        Ok(tool)
+    }
+
+    fn parse_cmake(
+        p_key: &str,
+        t: &DeTable<'_>,
+        span: Range<usize>,
+    ) -> Result<CMakePackage, SpannedError> {
+        let mut package = None;
+        let mut components = Vec::new();
+        let mut allow_unknown_version = false;
+
+        for (k_spanned, v) in t {
+            let k = k_spanned.as_ref().as_ref();
+            match (k, v.as_ref()) {
+                ("package", DeValue::String(s)) => {
+                    package = Some(s.clone().into_owned());
+                }
+                ("components", DeValue::Array(values)) => {
+                    components = Self::parse_name_list(&format!("{p_key}.{k}"), values)?;
+                }
+                ("allow-unknown-version", &DeValue::Boolean(allow)) => {
+                    allow_unknown_version = allow;
+                }
+                _ => {
+                    return Err(MetadataError::UnexpectedKey(
+                        p_key.to_owned(),
+                        k.to_owned(),
+                        v.as_ref().type_str().to_owned(),
+                    )
+                    .at(k_spanned.span()));
+                }
+            }
+        }
+
+        let package = package
+            .ok_or_else(|| MetadataError::MissingKey(format!("{p_key}.package")).at(span))?;
+        Ok(CMakePackage {
+            package,
+            components,
+            allow_unknown_version,
+        })
+    }
+
+    fn parse_inherit(
+        p_key: &str,
+        t: &DeTable<'_>,
+        span: Range<usize>,
+    ) -> Result<Inherit, SpannedError> {
+        let mut links = None;
+        let mut dependency = None;
+
+        for (k_spanned, v) in t {
+            let k = k_spanned.as_ref().as_ref();
+            match (k, v.as_ref()) {
+                ("links", DeValue::String(s)) => {
+                    links = Some(s.clone().into_owned());
+                }
+                ("dependency", DeValue::String(s)) => {
+                    dependency = Some(s.clone().into_owned());
+                }
+                _ => {
+                    return Err(MetadataError::UnexpectedKey(
+                        p_key.to_owned(),
+                        k.to_owned(),
+                        v.as_ref().type_str().to_owned(),
+                    )
+                    .at(k_spanned.span()));
+                }
+            }
+        }
+
+        let links =
+            links.ok_or_else(|| MetadataError::MissingKey(format!("{p_key}.links")).at(span))?;
+        Ok(Inherit { links, dependency })
+    }
+
+    fn parse_link_modifiers(
+        key: &str,
+        values: &DeArray<'_>,
+    ) -> Result<LinkModifiers, SpannedError> {
+        let modifiers = Self::parse_name_list(key, values)?;
+        for (i, (m, value)) in modifiers.iter().zip(values.iter()).enumerate() {
+            LinkModifiers::parse_modifier(m).map_err(|e| {
+                MetadataError::InvalidValue(format!("{key}[{i}]"), e).at(value.span())
+            })?;
+        }
+
+        Ok(LinkModifiers {
+            modifiers,
+            ..Default::default()
+        })
+    }
+
+    fn parse_alternatives(
+        key: &str,
+        values: &DeArray<'_>,
+    ) -> Result<Vec<Alternative>, SpannedError> {
+        let mut alternatives = Vec::new();
+
+        for (i, value) in values.iter().enumerate() {
+            let p_key = format!("{key}[{i}]");
+            let t = match value.as_ref() {
+                DeValue::Table(t) => t,
+                _ => return Err(MetadataError::NotATable(p_key).at(value.span())),
+            };
+
+            let mut name = None;
+            let mut version = None;
+            for (k_spanned, v) in t {
+                let k = k_spanned.as_ref().as_ref();
+                match (k, v.as_ref()) {
+                    ("name", DeValue::String(s)) => {
+                        name = Some(s.clone().into_owned());
+                    }
+                    ("version", DeValue::String(s)) => {
+                        VersionReq::parse(s).map_err(|e| {
+                            MetadataError::InvalidVersion(format!("{p_key}.{k}"), e).at(v.span())
+                        })?;
+
+                        version = Some(s.clone().into_owned());
+                    }
+                    _ => {
+                        return Err(MetadataError::UnexpectedKey(
+                            p_key,
+                            k.to_owned(),
+                            v.as_ref().type_str().to_owned(),
+                        )
+                        .at(k_spanned.span()));
+                    }
+                }
+            }
+
+            let name = name.ok_or_else(|| {
+                MetadataError::MissingKey(format!("{p_key}.name")).at(value.span())
+            })?;
+            let version = version.ok_or_else(|| {
+                MetadataError::MissingKey(format!("{p_key}.version")).at(value.span())
+            })?;
+            alternatives.push(Alternative { name, version });
+        }
+
+        Ok(alternatives)
+    }
+
+    fn parse_sources(key: &str, values: &DeArray<'_>) -> Result<Vec<ProbeSource>, SpannedError> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let key = format!("{key}[{i}]");
+                let s = value
+                    .as_ref()
+                    .as_str()
+                    .ok_or_else(|| MetadataError::NotString(key.clone()).at(value.span()))?;
+                s.parse()
+                    .map_err(|e| MetadataError::InvalidValue(key, e).at(value.span()))
+            })
+            .collect()
+    }
+
+    fn parse_name_list(key: &str, values: &DeArray<'_>) -> Result<Vec<String>, SpannedError> {
         values
             .iter()
             .enumerate()
@@ -362,64 +1362,40 @@ impl MetaData {
                     .as_ref()
                     .as_str()
                     .map(|x| x.to_owned())
-                    .ok_or_else(|| MetadataError::NotString(format!("{key}[{i}]")))
+                    .ok_or_else(|| MetadataError::NotString(format!("{key}[{i}]")).at(value.span()))
             })
             .collect()
     }
 }
 
-fn validate_version(version: &str) -> bool {
-    if let Some((min, max)) = version.split_once(',') {
-        if !min.trim_start().starts_with(">=") || !max.trim_start().starts_with('<') {
-            return false;
-        }
-
-        true
-    } else {
-        true
+// Combine two `cfg()` expressions so both have to match
+fn all_cfg(a: &CfgExpression, b: &CfgExpression) -> Result<CfgExpression, cfg_expr::ParseError> {
+    fn predicate(e: &CfgExpression) -> &str {
+        let s = e.as_str().trim();
+        s.strip_prefix("cfg(")
+            .and_then(|s| s.strip_suffix(')'))
+            .unwrap_or(s)
     }
-}
 
-#[derive(Debug, Clone)]
-pub(crate) enum VersionRange<'a> {
-    Range(std::ops::Range<&'a str>),
-    RangeFrom(std::ops::RangeFrom<&'a str>),
+    CfgExpression::parse(&format!("all({}, {})", predicate(a), predicate(b)))
 }
 
-impl<'a> std::ops::RangeBounds<&'a str> for VersionRange<'a> {
-    fn start_bound(&self) -> std::ops::Bound<&&'a str> {
-        match self {
-            VersionRange::Range(r) => r.start_bound(),
-            VersionRange::RangeFrom(r) => r.start_bound(),
-        }
-    }
+fn read_manifest(path: &Path) -> Result<String, crate::Error> {
+    let mut manifest = fs::File::open(path)
+        .map_err(|e| crate::Error::FailToRead(format!("error opening {}", path.display()), e))?;
 
-    fn end_bound(&self) -> std::ops::Bound<&&'a str> {
-        match self {
-            VersionRange::Range(r) => r.end_bound(),
-            VersionRange::RangeFrom(r) => r.end_bound(),
-        }
-    }
-}
+    let mut manifest_str = String::new();
+    manifest
+        .read_to_string(&mut manifest_str)
+        .map_err(|e| crate::Error::FailToRead(format!("error reading {}", path.display()), e))?;
 
-pub(crate) fn parse_version(version: &str) -> VersionRange<'_> {
-    if let Some((min, max)) = version.split_once(',') {
-        // Format checked when parsing
-        let min = min.trim_start().strip_prefix(">=").unwrap().trim();
-        let max = max.trim_start().strip_prefix('<').unwrap().trim();
-        VersionRange::Range(min..max)
-    } else if let Some(min) = version.trim_start().strip_prefix(">=") {
-        VersionRange::RangeFrom(min..)
-    } else {
-        VersionRange::RangeFrom(version..)
-    }
+    Ok(manifest_str)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use assert_matches::assert_matches;
-    use cfg_expr::Expression;
     use std::{env, path::PathBuf};
 
     fn parse_file(dir: &str) -> Result<MetaData, crate::Error> {
@@ -459,7 +1435,8 @@ mod tests {
                         feature: Some("another-test-feature".into()),
                         ..Default::default()
                     }
-                ]
+                ],
+                workspace_manifest: None,
             }
         )
     }
@@ -468,10 +1445,46 @@ mod tests {
     fn parse_feature_not_string() {
         assert_matches!(
             parse_file("toml-feature-not-string"),
-            Err(crate::Error::InvalidMetadata(_))
+            Err(crate::Error::InvalidMetadata(..))
         );
     }
 
+    #[test]
+    fn error_location() {
+        let err = parse_file("toml-feature-not-string").unwrap_err();
+        let location = match &err {
+            crate::Error::InvalidMetadata(_, Some(l)) => l,
+            _ => panic!("unexpected error: {:?}", err),
+        };
+
+        assert_eq!(location.line, 5);
+        assert_eq!(location.column, 28);
+        assert_eq!(
+            location.source_line,
+            "testlib = { version = \"1\", feature = 2 }"
+        );
+        assert!(err.to_string().ends_with(
+            r#"Cargo.toml:5:28: package.metadata.system-deps.testlib: unexpected key feature type integer
+  |
+5 | testlib = { version = "1", feature = 2 }
+  |                            ^^^^^^^"#
+        ));
+
+        let err = parse_file("toml-version-invalid").unwrap_err();
+        assert!(err.to_string().ends_with(
+            r#"
+  |
+5 | testlib = { version = ">= 1, =< 2" }
+  |                       ^^^^^^^^^^^^"#
+        ));
+
+        // The underline is as wide as the item in characters, not in bytes
+        let source = "a = \"héhé\" # comment\n";
+        let location = crate::MetadataLocation::new(Path::new("Cargo.toml"), source, 4..12);
+        assert_eq!(location.column, 5);
+        assert!(location.to_string().ends_with("  |     ^^^^^^"));
+    }
+
     #[test]
     fn parse_override_name() {
         let m = parse_file("toml-override-name").unwrap();
@@ -491,7 +1504,8 @@ mod tests {
                         optional: None,
                     }],
                     ..Default::default()
-                },]
+                },],
+                workspace_manifest: None,
             }
         )
     }
@@ -523,7 +1537,8 @@ mod tests {
                         },
                     ],
                     ..Default::default()
-                },]
+                },],
+                workspace_manifest: None,
             }
         )
     }
@@ -546,7 +1561,8 @@ mod tests {
                     ]),
                     version_overrides: vec![],
                     ..Default::default()
-                }]
+                }],
+                workspace_manifest: None,
             }
         )
     }
@@ -591,7 +1607,8 @@ mod tests {
                         },
                     ],
                     ..Default::default()
-                }]
+                }],
+                workspace_manifest: None,
             }
         )
     }
@@ -607,13 +1624,13 @@ mod tests {
                     Dependency {
                         key: "testbadger".into(),
                         version: Some("1".into()),
-                        optional: true,
+                        optional: Some(true),
                         ..Default::default()
                     },
                     Dependency {
                         key: "testlib".into(),
                         version: Some("1.0".into()),
-                        optional: true,
+                        optional: Some(true),
                         version_overrides: vec![VersionOverride {
                             key: "v5".into(),
                             version: "5.0".into(),
@@ -635,11 +1652,298 @@ mod tests {
                         },],
                         ..Default::default()
                     },
-                ]
+                ],
+                workspace_manifest: None,
+            }
+        )
+    }
+
+    #[test]
+    fn parse_config_tool() {
+        let m = parse_file("toml-config-tool").unwrap();
+
+        assert_eq!(
+            m.deps[0].config_tool,
+            Some(ConfigTool {
+                name: "../testconfig-config".into(),
+                version: vec!["--version".into()],
+                cflags: vec!["--cflags".into()],
+                libs: vec!["--ldflags".into(), "--libs".into(), "--system-libs".into()],
+                allow_unknown_version: false,
+            })
+        );
+        assert_eq!(
+            m.deps[1].config_tool,
+            Some(ConfigTool::new("../testconfig-config"))
+        );
+    }
+
+    #[test]
+    fn parse_cmake() {
+        let m = parse_file("toml-cmake").unwrap();
+
+        assert_eq!(
+            m.deps[0].cmake,
+            Some(CMakePackage {
+                package: "TestCMake".into(),
+                components: vec!["core".into(), "gui".into()],
+                allow_unknown_version: false,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_inherit() {
+        let m = parse_file("toml-inherit").unwrap();
+
+        assert_eq!(
+            m.deps[0].inherit,
+            Some(Inherit {
+                links: "test".into(),
+                dependency: None,
+            })
+        );
+        assert_eq!(
+            m.deps[1].inherit,
+            Some(Inherit {
+                links: "test".into(),
+                dependency: Some("testlib".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_search() {
+        let m = parse_file("toml-search").unwrap();
+
+        assert_eq!(
+            m.deps[0].search,
+            Some(Search {
+                libs: vec!["testsearch".into()],
+                headers: vec!["testsearch.h".into()],
+            })
+        );
+    }
+
+    #[test]
+    fn parse_version_cfgs() {
+        let m = parse_file("toml-version-cfgs").unwrap();
+
+        assert_eq!(m.deps[0].key, "testdata");
+        assert!(m.deps[0].version_cfgs.is_empty());
+        assert_eq!(m.deps[0].version_thresholds(), vec!["4.4", "5"]);
+        assert_eq!(m.deps[1].version_cfgs, vec!["1.0", "1.2", "1.2.4", "2"]);
+        assert_eq!(m.deps[1].version_thresholds(), m.deps[1].version_cfgs);
+
+        let err = parse_file("toml-version-cfgs-invalid").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("`package.metadata.system-deps.testlib.version-cfgs[1]`"));
+    }
+
+    #[test]
+    fn parse_link_modifiers() {
+        let m = parse_file("toml-link-modifiers").unwrap();
+
+        assert_eq!(
+            m.deps[0].link_modifiers,
+            vec![LinkModifiers {
+                lib: None,
+                link_name: None,
+                modifiers: vec![
+                    "+whole-archive".into(),
+                    "+verbatim".into(),
+                    "-as-needed".into(),
+                ],
+            }]
+        );
+        assert_eq!(
+            m.deps[1].link_modifiers,
+            vec![LinkModifiers {
+                lib: Some("teststatic".into()),
+                link_name: Some("foo".into()),
+                modifiers: vec![
+                    "+whole-archive".into(),
+                    "-bundle".into(),
+                    "+as-needed".into(),
+                ],
+            }]
+        );
+
+        assert_eq!(
+            "+bundle,-as-needed=foo".parse::<LinkModifiers>().unwrap(),
+            LinkModifiers {
+                lib: Some("foo".into()),
+                link_name: None,
+                modifiers: vec!["+bundle".into(), "-as-needed".into()],
             }
+        );
+        assert!("whole-archive".parse::<LinkModifiers>().is_err());
+    }
+
+    #[test]
+    fn parse_version_header() {
+        let m = parse_file("toml-version-header").unwrap();
+
+        assert_eq!(
+            m.deps[0].version_header,
+            Some(VersionHeader {
+                file: "testheader.h".into(),
+                major: "TESTHEADER_MAJOR".into(),
+                minor: "TESTHEADER_MINOR".into(),
+                micro: Some("TESTHEADER_MICRO".into()),
+            })
+        );
+        assert_eq!(m.deps[1].version_header.as_ref().unwrap().micro, None,);
+    }
+
+    #[test]
+    fn parse_alternatives() {
+        let m = parse_file("toml-alternatives").unwrap();
+
+        assert_eq!(
+            m.deps,
+            vec![
+                Dependency {
+                    key: "testdata".into(),
+                    optional: Some(true),
+                    alternatives: vec![Alternative {
+                        name: "testdata-notexist".into(),
+                        version: "4".into(),
+                    }],
+                    ..Default::default()
+                },
+                Dependency {
+                    key: "testlib".into(),
+                    alternatives: vec![
+                        Alternative {
+                            name: "testlib".into(),
+                            version: "5".into(),
+                        },
+                        Alternative {
+                            name: "testlib-notexist".into(),
+                            version: "1".into(),
+                        },
+                        Alternative {
+                            name: "testlib-3.0".into(),
+                            version: "3".into(),
+                        },
+                        Alternative {
+                            name: "testlib-2.0".into(),
+                            version: "2".into(),
+                        },
+                    ],
+                    ..Default::default()
+                },
+            ]
         )
     }
 
+    #[test]
+    fn parse_cfg_override() {
+        let m = parse_file("toml-cfg-override").unwrap();
+
+        assert_eq!(
+            m.deps,
+            vec![
+                Dependency {
+                    key: "testanotherlib".into(),
+                    version: Some("1".into()),
+                    cfg: Some(CfgExpression::parse("all(unix, target_arch = \"x86_64\")").unwrap()),
+                    ..Default::default()
+                },
+                Dependency {
+                    key: "testdata".into(),
+                    version: Some("4".into()),
+                    cfg: Some(CfgExpression::parse("target_os = \"linux\"").unwrap()),
+                    ..Default::default()
+                },
+                Dependency {
+                    key: "testlib".into(),
+                    version: Some("1".into()),
+                    cfg_overrides: vec![
+                        CfgOverride {
+                            cfg: CfgExpression::parse("cfg(target_env = \"msvc\")").unwrap(),
+                            version: None,
+                            name: Some("testlib-notexist".into()),
+                            fallback_names: None,
+                            optional: Some(true),
+                        },
+                        CfgOverride {
+                            cfg: CfgExpression::parse("cfg(target_os = \"linux\")").unwrap(),
+                            version: Some("2".into()),
+                            name: Some("testlib-2.0".into()),
+                            fallback_names: None,
+                            optional: None,
+                        },
+                    ],
+                    ..Default::default()
+                },
+            ]
+        );
+        assert_eq!(
+            m.deps[2].cfg_overrides[1].cfg.as_str(),
+            "target_os = \"linux\""
+        );
+    }
+
+    #[test]
+    fn parse_workspace() {
+        let m = parse_file("toml-workspace/member").unwrap();
+
+        assert_eq!(
+            m.deps,
+            vec![
+                // The member made it required
+                Dependency {
+                    key: "testanotherlib".into(),
+                    version: Some("1".into()),
+                    optional: Some(false),
+                    workspace: true,
+                    ..Default::default()
+                },
+                Dependency {
+                    key: "testdata".into(),
+                    version: Some("5".into()),
+                    feature: Some("test-feature".into()),
+                    optional: Some(true),
+                    workspace: true,
+                    ..Default::default()
+                },
+                Dependency {
+                    key: "testlib".into(),
+                    version: Some("1".into()),
+                    version_overrides: vec![VersionOverride {
+                        key: "v2".into(),
+                        version: "2.0".into(),
+                        name: Some("testlib-2.0".into()),
+                        fallback_names: None,
+                        optional: None,
+                    }],
+                    workspace: true,
+                    ..Default::default()
+                },
+            ]
+        );
+        assert!(m
+            .workspace_manifest
+            .unwrap()
+            .ends_with(Path::new("toml-workspace").join("Cargo.toml")));
+    }
+
+    #[test]
+    fn parse_workspace_errors() {
+        let err = parse_file("toml-workspace/member-not-in-workspace").unwrap_err();
+        assert!(err.to_string().contains(
+            "`package.metadata.system-deps.testmore`: not defined in `workspace.metadata.system-deps`"
+        ));
+
+        let err = parse_file("toml-workspace/member-unexpected-key").unwrap_err();
+        assert!(err.to_string().contains(
+            "package.metadata.system-deps.testlib: key name cannot be used with `workspace = true`"
+        ));
+    }
+
     #[test]
     fn parse_os_specific() {
         let m = parse_file("toml-os-specific").unwrap();
@@ -651,23 +1955,24 @@ mod tests {
                     Dependency {
                         key: "testlib".into(),
                         version: Some("1".into()),
-                        cfg: Some(Expression::parse("not(target_os = \"macos\")").unwrap()),
+                        cfg: Some(CfgExpression::parse("not(target_os = \"macos\")").unwrap()),
                         ..Default::default()
                     },
                     Dependency {
                         key: "testdata".into(),
                         version: Some("1".into()),
-                        cfg: Some(Expression::parse("target_os = \"linux\"").unwrap()),
+                        cfg: Some(CfgExpression::parse("target_os = \"linux\"").unwrap()),
                         ..Default::default()
                     },
                     Dependency {
                         key: "testanotherlib".into(),
                         version: Some("1".into()),
-                        cfg: Some(Expression::parse("unix").unwrap()),
-                        optional: true,
+                        cfg: Some(CfgExpression::parse("unix").unwrap()),
+                        optional: Some(true),
                         ..Default::default()
                     },
-                ]
+                ],
+                workspace_manifest: None,
             }
         )
     }
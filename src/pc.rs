@@ -0,0 +1,767 @@
+// Built-in resolver of `.pc` files, used instead of the `pkg-config` binary
+
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{cross, version::VersionReq, EnvVariables, EnvVariablesExt};
+
+/// The flags and version of a library as reported by `pkg-config`, using either backend.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct PcLibrary {
+    pub(crate) libs: Vec<String>,
+    pub(crate) link_paths: Vec<PathBuf>,
+    pub(crate) frameworks: Vec<String>,
+    pub(crate) framework_paths: Vec<PathBuf>,
+    pub(crate) include_paths: Vec<PathBuf>,
+    pub(crate) ld_args: Vec<Vec<String>>,
+    pub(crate) defines: HashMap<String, Option<String>>,
+    pub(crate) version: String,
+}
+
+impl From<pkg_config::Library> for PcLibrary {
+    fn from(l: pkg_config::Library) -> Self {
+        Self {
+            libs: l.libs,
+            link_paths: l.link_paths,
+            frameworks: l.frameworks,
+            framework_paths: l.framework_paths,
+            include_paths: l.include_paths,
+            ld_args: l.ld_args,
+            defines: l.defines,
+            version: l.version,
+        }
+    }
+}
+
+/// Resolve packages by reading `.pc` files, following the rules of `pkgconf`.
+#[derive(Debug, Clone)]
+pub(crate) struct Resolver {
+    search_paths: Vec<PathBuf>,
+    sysroot: Option<PathBuf>,
+    system_lib_dirs: Vec<String>,
+    is_msvc: bool,
+    statik: bool,
+}
+
+impl Resolver {
+    pub(crate) fn new(env: &EnvVariables) -> Self {
+        let target = env.get("TARGET");
+        let multiarch = target.as_deref().and_then(multiarch);
+        let sysroot = cross::sysroot(env);
+        let split = |var: &str| -> Option<Vec<PathBuf>> {
+            cross::targeted_var(env, var).map(|v| {
+                env::split_paths(&v)
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .collect()
+            })
+        };
+
+        let mut search_paths = split("PKG_CONFIG_PATH").unwrap_or_default();
+        search_paths.extend(split("PKG_CONFIG_LIBDIR").unwrap_or_else(|| {
+            let mut dirs = default_search_paths(target.as_deref(), multiarch.as_deref());
+            // The default directories of the host can't be used for the target
+            if cross::is_cross(env) {
+                dirs = dirs
+                    .iter()
+                    .map(|d| cross::with_sysroot(sysroot.as_deref(), d))
+                    .collect();
+            }
+            dirs
+        }));
+
+        // System library paths are not reported, as the binary backend does,
+        // but system include paths are.
+        let system_lib_dirs = if env.contains("PKG_CONFIG_ALLOW_SYSTEM_LIBS") {
+            Vec::new()
+        } else if let Some(paths) = split("PKG_CONFIG_SYSTEM_LIBRARY_PATH") {
+            paths
+                .into_iter()
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect()
+        } else {
+            let mut dirs = ["/lib", "/lib64", "/usr/lib", "/usr/lib64"]
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>();
+            if let Some(multiarch) = &multiarch {
+                dirs.push(format!("/lib/{multiarch}"));
+                dirs.push(format!("/usr/lib/{multiarch}"));
+            }
+            dirs
+        };
+
+        Self {
+            search_paths,
+            sysroot,
+            system_lib_dirs,
+            is_msvc: target.is_some_and(|t| t.contains("msvc")),
+            statik: false,
+        }
+    }
+
+    /// Look for `.pc` files in `path` before the other search paths.
+    pub(crate) fn prepend_search_path(&mut self, path: &Path) -> &mut Self {
+        self.search_paths.insert(0, path.to_path_buf());
+        self
+    }
+
+    /// Only look for `.pc` files in `paths`.
+    pub(crate) fn set_search_paths(&mut self, paths: Vec<PathBuf>) -> &mut Self {
+        self.search_paths = paths;
+        self
+    }
+
+    pub(crate) fn statik(&mut self, statik: bool) -> &mut Self {
+        self.statik = statik;
+        self
+    }
+
+    pub(crate) fn probe(&self, name: &str) -> Result<PcLibrary, String> {
+        let package = self.load(name)?;
+
+        // Cflags of all the required packages are needed, even private ones
+        let mut cflags = Vec::new();
+        self.walk(&package, true, &mut HashSet::new(), &mut |p| {
+            cflags.extend(p.cflags.iter().cloned());
+        })?;
+
+        let mut libs = Vec::new();
+        self.walk(&package, self.statik, &mut HashSet::new(), &mut |p| {
+            libs.extend(p.libs.iter().cloned());
+            if self.statik {
+                libs.extend(p.libs_private.iter().cloned());
+            }
+        })?;
+
+        let mut lib = PcLibrary {
+            version: package.version.clone(),
+            ..Default::default()
+        };
+        self.parse_flags(&mut lib, &dedup(cflags));
+        self.parse_flags(&mut lib, &libs);
+
+        Ok(lib)
+    }
+
+    /// The `.pc` file of `name` and the ones of all its requirements, including private ones.
+    /// Packages which can't be found are ignored.
+    pub(crate) fn files(&self, name: &str) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        if let Ok(package) = self.load(name) {
+            let _ = self.walk(&package, true, &mut HashSet::new(), &mut |p| {
+                files.push(p.path.clone());
+            });
+        }
+        files
+    }
+
+    /// Parse flags printed by other tools, such as `*-config` scripts.
+    pub(crate) fn parse_output(&self, output: &str) -> Result<PcLibrary, String> {
+        Ok(self.parse_words(&split_words(output)?))
+    }
+
+    /// Parse flags which have already been split into words.
+    pub(crate) fn parse_words(&self, words: &[String]) -> PcLibrary {
+        let mut lib = PcLibrary::default();
+        self.parse_flags(&mut lib, words);
+        lib
+    }
+
+    // Call `f` on `package` and its requirements, depth first
+    fn walk(
+        &self,
+        package: &Package,
+        private: bool,
+        visited: &mut HashSet<String>,
+        f: &mut dyn FnMut(&Package),
+    ) -> Result<(), String> {
+        if !visited.insert(package.name.clone()) {
+            return Ok(());
+        }
+        f(package);
+
+        let requires = package.requires.iter();
+        let requires_private = package.requires_private.iter().filter(|_| private);
+        for (name, version) in requires.chain(requires_private) {
+            let required = self.load(name)?;
+            if let Some(version) = version {
+                let req = VersionReq::parse(version)
+                    .map_err(|e| format!("{}: invalid requirement on {name}: {e}", package.name))?;
+                if !req.matches(&required.version) {
+                    return Err(format!(
+                        "Package dependency requirement '{name} {version}' could not be satisfied.\n\
+                        Package '{name}' has version '{}', required version is '{version}'",
+                        required.version
+                    ));
+                }
+            }
+            self.walk(&required, private, visited, f)?;
+        }
+
+        Ok(())
+    }
+
+    fn load(&self, name: &str) -> Result<Package, String> {
+        let file = format!("{name}.pc");
+        let path = self
+            .search_paths
+            .iter()
+            .map(|dir| dir.join(&file))
+            .find(|p| p.is_file())
+            .ok_or_else(|| {
+                format!(
+                    "Package {name} was not found in the pkg-config search path {:?}",
+                    self.search_paths
+                )
+            })?;
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+
+        Package::parse(name, &path, &content, self.sysroot.as_deref())
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    // Parse the flags the same way as the `pkg-config` crate
+    fn parse_flags(&self, lib: &mut PcLibrary, words: &[String]) {
+        let mut iter = words.iter().map(|w| w.as_str());
+        while let Some(word) = iter.next() {
+            if let Some(path) = word.strip_prefix("-L").filter(|p| !p.is_empty()) {
+                if !self.system_lib_dirs.iter().any(|d| d == path) {
+                    lib.link_paths.push(self.with_sysroot(path));
+                }
+            } else if let Some(path) = word.strip_prefix("-F").filter(|p| !p.is_empty()) {
+                lib.framework_paths.push(self.with_sysroot(path));
+            } else if let Some(path) = word.strip_prefix("-I").filter(|p| !p.is_empty()) {
+                lib.include_paths.push(self.with_sysroot(path));
+            } else if let Some(name) = word.strip_prefix("-l").filter(|p| !p.is_empty()) {
+                // These are provided by the CRT with MSVC
+                if !(self.is_msvc && ["m", "c", "pthread"].contains(&name)) {
+                    lib.libs.push(name.to_string());
+                }
+            } else if let Some(define) = word.strip_prefix("-D").filter(|p| !p.is_empty()) {
+                let mut iter = define.split('=');
+                lib.defines.insert(
+                    iter.next().unwrap().to_owned(),
+                    iter.next().map(|s| s.to_owned()),
+                );
+            } else if let Some(options) = word.strip_prefix("-Wl,") {
+                let mut options = options.split(',');
+                let mut ld_option = Vec::new();
+                while let Some(option) = options.next() {
+                    if option == "-framework" {
+                        if let Some(framework) = options.next() {
+                            lib.frameworks.push(framework.to_string());
+                        }
+                    } else {
+                        ld_option.push(option.to_string());
+                    }
+                }
+                lib.ld_args.push(ld_option);
+            } else if word == "-framework" {
+                if let Some(framework) = iter.next() {
+                    lib.frameworks.push(framework.to_string());
+                }
+            } else if ["-isystem", "-iquote", "-idirafter"].contains(&word) {
+                if let Some(path) = iter.next() {
+                    lib.include_paths.push(self.with_sysroot(path));
+                }
+            }
+        }
+    }
+
+    fn with_sysroot(&self, path: &str) -> PathBuf {
+        cross::with_sysroot(self.sysroot.as_deref(), Path::new(path))
+    }
+}
+
+/// Run a `pkg-config` binary with the settings of the target, used when cross-compiling.
+#[derive(Debug, Clone)]
+pub(crate) struct Command {
+    exe: PathBuf,
+    vars: Vec<(&'static str, String)>,
+    // Used to parse the flags printed by the binary
+    resolver: Resolver,
+    statik: bool,
+}
+
+impl Command {
+    pub(crate) fn new(env: &EnvVariables) -> Self {
+        let mut vars = Vec::new();
+        for var in ["PKG_CONFIG_PATH", "PKG_CONFIG_LIBDIR"] {
+            if let Some(value) = cross::targeted_var(env, var) {
+                vars.push((var, value));
+            }
+        }
+        if let Some(sysroot) = cross::sysroot(env) {
+            vars.push((
+                "PKG_CONFIG_SYSROOT_DIR",
+                sysroot.to_string_lossy().into_owned(),
+            ));
+        }
+
+        Self {
+            exe: cross::pkg_config_exe(env).unwrap_or_else(|| PathBuf::from("pkg-config")),
+            vars,
+            resolver: Resolver::new(env),
+            statik: false,
+        }
+    }
+
+    pub(crate) fn statik(&mut self, statik: bool) -> &mut Self {
+        self.statik = statik;
+        self
+    }
+
+    /// Look for `.pc` files in `path` before the other search paths.
+    pub(crate) fn prepend_search_path(&mut self, path: &Path) -> &mut Self {
+        let mut paths = vec![path.to_path_buf()];
+        let var = self
+            .vars
+            .iter()
+            .position(|(var, _)| *var == "PKG_CONFIG_PATH");
+        if let Some(i) = var {
+            paths.extend(env::split_paths(&self.vars.remove(i).1));
+        }
+        if let Ok(paths) = env::join_paths(paths) {
+            self.vars
+                .push(("PKG_CONFIG_PATH", paths.to_string_lossy().into_owned()));
+        }
+        self
+    }
+
+    pub(crate) fn probe(&self, name: &str) -> Result<PcLibrary, String> {
+        let mut args = vec!["--libs", "--cflags"];
+        if self.statik {
+            args.push("--static");
+        }
+        let flags = self.run(&args, name)?;
+        let version = self.run(&["--modversion"], name)?;
+
+        let mut lib = self.resolver.parse_output(&flags)?;
+        lib.version = version.trim().to_string();
+        Ok(lib)
+    }
+
+    /// The `.pc` file of `name` and the ones of all its requirements, including private ones,
+    /// as located by the binary. Packages which can't be found are ignored.
+    pub(crate) fn files(&self, name: &str) -> Vec<PathBuf> {
+        // Some implementations prepend the sysroot to the value of `pcfiledir`
+        let mut cmd = self.clone();
+        cmd.vars.retain(|(var, _)| *var != "PKG_CONFIG_SYSROOT_DIR");
+        cmd.vars.push(("PKG_CONFIG_SYSROOT_DIR", String::new()));
+
+        // Walking the requirements runs the binary several times per package, so reuse the
+        // files found by the resolver if it reads the same `.pc` file as the binary
+        let Ok(dir) = cmd.run(&["--variable=pcfiledir"], name) else {
+            return Vec::new();
+        };
+        let file = Path::new(dir.trim()).join(format!("{name}.pc"));
+        let files = self.resolver.files(name);
+        if files.first() == Some(&file) {
+            return files;
+        }
+
+        let mut files = Vec::new();
+        cmd.walk_files(name, &mut HashSet::new(), &mut files);
+        files
+    }
+
+    // Add the `.pc` file of `name` then the ones of its requirements, depth first
+    fn walk_files(&self, name: &str, visited: &mut HashSet<String>, files: &mut Vec<PathBuf>) {
+        if !visited.insert(name.to_string()) {
+            return;
+        }
+        let Ok(dir) = self.run(&["--variable=pcfiledir"], name) else {
+            return;
+        };
+        files.push(Path::new(dir.trim()).join(format!("{name}.pc")));
+
+        for arg in ["--print-requires", "--print-requires-private"] {
+            let requires = self.run(&[arg], name).unwrap_or_default();
+            // One requirement per line, possibly followed by a version constraint
+            for required in requires.lines().filter_map(|l| l.split_whitespace().next()) {
+                self.walk_files(required, visited, files);
+            }
+        }
+    }
+
+    fn run(&self, args: &[&str], name: &str) -> Result<String, String> {
+        let mut cmd = std::process::Command::new(&self.exe);
+        cmd.args(args).arg(name);
+        for (var, value) in self.vars.iter() {
+            cmd.env(var, value);
+        }
+
+        let output = cmd
+            .output()
+            .map_err(|e| format!("Failed to run {cmd:?}: {e}"))?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(format!(
+                "{cmd:?} failed:\n{}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ))
+        }
+    }
+}
+
+// The directories searched by default by `pkg-config` for the target, as configured by
+// the distributions and package managers of each platform
+fn default_search_paths(target: Option<&str>, multiarch: Option<&str>) -> Vec<PathBuf> {
+    // Use the host platform if the target is unknown
+    let is = |os: &str, host: bool| target.map_or(host, |t| t.contains(os));
+    let paths = |dirs: &[&str]| dirs.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+    if is("apple", cfg!(target_vendor = "apple")) {
+        // Homebrew on Apple silicon and Intel, then MacPorts
+        paths(&[
+            "/opt/homebrew/lib/pkgconfig",
+            "/opt/homebrew/share/pkgconfig",
+            "/usr/local/lib/pkgconfig",
+            "/usr/local/share/pkgconfig",
+            "/opt/local/lib/pkgconfig",
+            "/usr/lib/pkgconfig",
+        ])
+    } else if is("bsd", cfg!(target_os = "freebsd")) || is("dragonfly", false) {
+        paths(&[
+            "/usr/local/libdata/pkgconfig",
+            "/usr/local/lib/pkgconfig",
+            "/usr/libdata/pkgconfig",
+        ])
+    } else if is("windows", cfg!(windows)) {
+        // There is no standard location, `PKG_CONFIG_PATH` has to be defined
+        Vec::new()
+    } else {
+        let mut dirs = paths(&["/usr/local/lib/pkgconfig", "/usr/local/share/pkgconfig"]);
+        if let Some(multiarch) = multiarch {
+            dirs.push(Path::new("/usr/lib").join(multiarch).join("pkgconfig"));
+        }
+        dirs.extend(paths(&[
+            "/usr/lib64/pkgconfig",
+            "/usr/lib/pkgconfig",
+            "/usr/share/pkgconfig",
+        ]));
+        dirs
+    }
+}
+
+#[derive(Debug, Default)]
+struct Package {
+    name: String,
+    version: String,
+    cflags: Vec<String>,
+    libs: Vec<String>,
+    libs_private: Vec<String>,
+    requires: Vec<(String, Option<String>)>,
+    requires_private: Vec<(String, Option<String>)>,
+    path: PathBuf,
+}
+
+impl Package {
+    fn parse(
+        name: &str,
+        path: &Path,
+        content: &str,
+        sysroot: Option<&Path>,
+    ) -> Result<Self, String> {
+        let mut variables = HashMap::new();
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        variables.insert("pcfiledir".to_string(), dir.to_string_lossy().into_owned());
+        variables.insert(
+            "pc_sysrootdir".to_string(),
+            sysroot.map_or("/".to_string(), |s| s.to_string_lossy().into_owned()),
+        );
+
+        let mut package = Package {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+
+        for line in logical_lines(content) {
+            let line = line.trim();
+            let Some(sep) = line.find([':', '=']) else {
+                continue;
+            };
+            let (key, value) = (line[..sep].trim(), &line[sep + 1..]);
+            if key.is_empty()
+                || !key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+            {
+                continue;
+            }
+            let value = expand(value.trim(), &variables)?;
+
+            if line.as_bytes()[sep] == b'=' {
+                variables.insert(key.to_string(), value);
+                continue;
+            }
+
+            match key {
+                "Version" => package.version = value,
+                "Cflags" | "CFlags" => package.cflags = split_words(&value)?,
+                "Libs" => package.libs = split_words(&value)?,
+                "Libs.private" => package.libs_private = split_words(&value)?,
+                "Requires" => package.requires = parse_requires(&value)?,
+                "Requires.private" => package.requires_private = parse_requires(&value)?,
+                _ => {}
+            }
+        }
+
+        Ok(package)
+    }
+}
+
+// Join lines ending with a backslash and strip comments
+fn logical_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for line in content.lines() {
+        let mut out = String::new();
+        let mut comment = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&'#') => {
+                    out.push('#');
+                    chars.next();
+                }
+                '#' => {
+                    comment = true;
+                    break;
+                }
+                _ => out.push(c),
+            }
+        }
+
+        match out.strip_suffix('\\') {
+            Some(out) if !comment => current.push_str(out),
+            _ => {
+                current.push_str(&out);
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
+
+// Expand `${variable}` references
+fn expand(value: &str, variables: &HashMap<String, String>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = value;
+
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+
+        if let Some(r) = rest.strip_prefix('$') {
+            result.push('$');
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix('{') {
+            let end = r
+                .find('}')
+                .ok_or_else(|| format!("unterminated variable reference in `{value}`"))?;
+            let name = &r[..end];
+            let v = variables
+                .get(name)
+                .ok_or_else(|| format!("undefined variable `{name}`"))?;
+            result.push_str(v);
+            rest = &r[end + 1..];
+        } else {
+            result.push('$');
+        }
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+// Split flags as a shell would, handling quotes and escaped characters
+fn split_words(value: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', Some('\'')) => word.push(c),
+            ('\\', _) => {
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+                in_word = true;
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => word.push(c),
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (c, None) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (c, None) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(format!("unterminated quote in `{value}`"));
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+// Parse a list of packages such as `glib-2.0 >= 2.50, gobject-2.0`
+fn parse_requires(value: &str) -> Result<Vec<(String, Option<String>)>, String> {
+    let mut requires = Vec::new();
+    let mut tokens = value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+        .peekable();
+
+    while let Some(name) = tokens.next() {
+        let version = match tokens.peek() {
+            Some(&op) if ["=", "==", "!=", "<", ">", "<=", ">="].contains(&op) => {
+                tokens.next();
+                let version = tokens
+                    .next()
+                    .ok_or_else(|| format!("missing version after `{name} {op}`"))?;
+                Some(format!("{op} {version}"))
+            }
+            _ => None,
+        };
+        requires.push((name.to_string(), version));
+    }
+
+    Ok(requires)
+}
+
+// Remove duplicated include paths and defines, keeping the first occurrence.
+// Libraries are not deduplicated as their order matters when linking.
+fn dedup(words: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    words
+        .into_iter()
+        .filter(|w| !(w.starts_with("-I") || w.starts_with("-D")) || seen.insert(w.clone()))
+        .collect()
+}
+
+/// The Debian multiarch tuple of `target`, such as `x86_64-linux-gnu`.
+pub(crate) fn multiarch(target: &str) -> Option<String> {
+    let parts = target.split('-').collect::<Vec<_>>();
+    let (arch, os, abi) = match parts.as_slice() {
+        [arch, _vendor, os, abi] => (*arch, *os, *abi),
+        [arch, os, abi] => (*arch, *os, *abi),
+        _ => return None,
+    };
+    if os != "linux" {
+        return None;
+    }
+
+    let arch = match arch {
+        "i586" | "i686" => "i386",
+        a if a.starts_with("armv") || a == "arm" => "arm",
+        a => a,
+    };
+
+    Some(format!("{arch}-{os}-{abi}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_paths() {
+        let paths = |target| default_search_paths(Some(target), multiarch(target).as_deref());
+
+        assert!(
+            paths("aarch64-apple-darwin").contains(&PathBuf::from("/opt/homebrew/lib/pkgconfig"))
+        );
+        assert!(paths("x86_64-unknown-freebsd")
+            .contains(&PathBuf::from("/usr/local/libdata/pkgconfig")));
+        assert!(paths("x86_64-pc-windows-msvc").is_empty());
+        assert!(paths("aarch64-unknown-linux-gnu")
+            .contains(&PathBuf::from("/usr/lib/aarch64-linux-gnu/pkgconfig")));
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(
+            split_words(r#"-I/usr/include -DFOO="a b" -I'/opt/my dir' -L/my\ dir"#).unwrap(),
+            vec!["-I/usr/include", "-DFOO=a b", "-I/opt/my dir", "-L/my dir"]
+        );
+        assert!(split_words("-I'/usr/include").is_err());
+    }
+
+    #[test]
+    fn requires() {
+        assert_eq!(
+            parse_requires("glib-2.0 >= 2.50, gobject-2.0,gio-2.0 = 2.60 zlib").unwrap(),
+            vec![
+                ("glib-2.0".into(), Some(">= 2.50".into())),
+                ("gobject-2.0".into(), None),
+                ("gio-2.0".into(), Some("= 2.60".into())),
+                ("zlib".into(), None),
+            ]
+        );
+        assert!(parse_requires("glib-2.0 >=").is_err());
+    }
+
+    #[test]
+    fn variables() {
+        let mut variables = HashMap::new();
+        variables.insert("prefix".to_string(), "/usr".to_string());
+        assert_eq!(
+            expand("${prefix}/lib $$HOME", &variables).unwrap(),
+            "/usr/lib $HOME"
+        );
+        assert!(expand("${libdir}", &variables).is_err());
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(
+            logical_lines("a=1 # comment\nLibs: -la \\\n -lb\n# comment\nb=\\#1"),
+            vec!["a=1 ", "Libs: -la  -lb", "", "b=#1"]
+        );
+    }
+
+    #[test]
+    fn multiarch_tuple() {
+        assert_eq!(
+            multiarch("x86_64-unknown-linux-gnu").as_deref(),
+            Some("x86_64-linux-gnu")
+        );
+        assert_eq!(
+            multiarch("armv7-unknown-linux-gnueabihf").as_deref(),
+            Some("arm-linux-gnueabihf")
+        );
+        assert_eq!(
+            multiarch("i686-unknown-linux-gnu").as_deref(),
+            Some("i386-linux-gnu")
+        );
+        assert_eq!(multiarch("x86_64-apple-darwin"), None);
+    }
+}
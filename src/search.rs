@@ -0,0 +1,176 @@
+// Look for libraries and headers directly in the library and include directories
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cross, metadata::Search, pc, split_paths, EnvVariable, EnvVariables, EnvVariablesExt,
+    InternalLib, Library, Source,
+};
+
+pub(crate) struct Searcher {
+    lib_dirs: Vec<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    system_lib_dirs: Vec<PathBuf>,
+    lib_files: fn(&str) -> (Vec<String>, String),
+}
+
+impl Searcher {
+    pub(crate) fn new(env: &EnvVariables, name: &str) -> Self {
+        let target = env.get("TARGET").unwrap_or_default();
+        let multiarch = pc::multiarch(&target);
+        let split = |value: Option<String>| value.map(|v| split_paths(&v)).unwrap_or_default();
+
+        let mut system_lib_dirs = Vec::new();
+        let mut system_include_dirs = vec![PathBuf::from("/usr/local/include")];
+        if let Some(multiarch) = &multiarch {
+            system_lib_dirs.push(Path::new("/usr/lib").join(multiarch));
+            system_lib_dirs.push(Path::new("/lib").join(multiarch));
+            system_include_dirs.push(Path::new("/usr/include").join(multiarch));
+        }
+        system_lib_dirs.extend(
+            ["/usr/lib64", "/usr/lib", "/lib64", "/lib"]
+                .iter()
+                .map(PathBuf::from),
+        );
+        system_include_dirs.push(PathBuf::from("/usr/include"));
+
+        // The system directories of the host can't be used for the target
+        if cross::is_cross(env) {
+            let sysroot = cross::sysroot(env);
+            for dir in system_lib_dirs
+                .iter_mut()
+                .chain(system_include_dirs.iter_mut())
+            {
+                *dir = cross::with_sysroot(sysroot.as_deref(), dir);
+            }
+        }
+
+        // Directories defined by the user are searched first
+        let mut lib_dirs = split(env.get(&EnvVariable::new_search_native(name)));
+        lib_dirs.extend(split(env.get("LIBRARY_PATH")));
+        lib_dirs.push(PathBuf::from("/usr/local/lib"));
+        lib_dirs.extend(system_lib_dirs.iter().cloned());
+
+        let mut include_dirs = split(env.get(&EnvVariable::new_include(name)));
+        include_dirs.extend(split(env.get("CPATH")));
+        include_dirs.extend(split(env.get("C_INCLUDE_PATH")));
+        include_dirs.extend(system_include_dirs);
+
+        Self {
+            lib_dirs,
+            include_dirs,
+            system_lib_dirs,
+            lib_files: lib_files(&target),
+        }
+    }
+
+    /// Only look in `lib_dirs` and `include_dirs`.
+    pub(crate) fn in_dirs(
+        env: &EnvVariables,
+        lib_dirs: Vec<PathBuf>,
+        include_dirs: Vec<PathBuf>,
+    ) -> Self {
+        let target = env.get("TARGET").unwrap_or_default();
+
+        Self {
+            lib_dirs,
+            include_dirs,
+            system_lib_dirs: Vec::new(),
+            lib_files: lib_files(&target),
+        }
+    }
+
+    /// The directories searched for headers, in order.
+    pub(crate) fn include_dirs(&self) -> &[PathBuf] {
+        &self.include_dirs
+    }
+
+    /// Look for the libraries and headers listed in `search`.
+    pub(crate) fn find(&self, name: &str, search: &Search) -> Result<Library, String> {
+        let mut libs = Vec::new();
+        let mut link_paths = Vec::new();
+        for lib in search.libs.iter() {
+            let (files, static_file) = (self.lib_files)(lib);
+            let dir = self
+                .lib_dirs
+                .iter()
+                .find(|dir| files.iter().any(|f| dir.join(f).is_file()))
+                .ok_or_else(|| format!("library {lib} not found in {:?}", self.lib_dirs))?;
+            let is_static_available = dir.join(static_file).is_file();
+
+            // The linker already looks in the system directories
+            if !self.system_lib_dirs.contains(dir) && !link_paths.contains(dir) {
+                link_paths.push(dir.clone());
+            }
+            libs.push(InternalLib::new(lib.clone(), is_static_available));
+        }
+
+        let mut include_paths = Vec::new();
+        for header in search.headers.iter() {
+            let dir = self
+                .include_dirs
+                .iter()
+                .find(|dir| dir.join(header).is_file())
+                .ok_or_else(|| format!("header {header} not found in {:?}", self.include_dirs))?;
+
+            if !include_paths.contains(dir) {
+                include_paths.push(dir.clone());
+            }
+        }
+
+        Ok(Library {
+            name: name.to_string(),
+            source: Source::Search,
+            libs,
+            link_paths,
+            frameworks: Vec::new(),
+            framework_paths: Vec::new(),
+            include_paths,
+            ld_args: Vec::new(),
+            defines: HashMap::new(),
+            version: String::new(),
+            statik: false,
+            alternative: None,
+            pc_file: None,
+        })
+    }
+}
+
+fn lib_files(target: &str) -> fn(&str) -> (Vec<String>, String) {
+    if target.contains("windows") {
+        windows_lib_files
+    } else if target.contains("apple") {
+        apple_lib_files
+    } else {
+        unix_lib_files
+    }
+}
+
+// The files providing a library, in order of preference, and the static library
+fn unix_lib_files(lib: &str) -> (Vec<String>, String) {
+    let a = format!("lib{lib}.a");
+    (vec![format!("lib{lib}.so"), a.clone()], a)
+}
+
+fn apple_lib_files(lib: &str) -> (Vec<String>, String) {
+    let a = format!("lib{lib}.a");
+    (
+        vec![
+            format!("lib{lib}.dylib"),
+            format!("lib{lib}.tbd"),
+            a.clone(),
+        ],
+        a,
+    )
+}
+
+fn windows_lib_files(lib: &str) -> (Vec<String>, String) {
+    let a = format!("lib{lib}.a");
+    (
+        vec![format!("{lib}.lib"), format!("lib{lib}.dll.a"), a.clone()],
+        a,
+    )
+}
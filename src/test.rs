@@ -13,7 +13,9 @@ use assert_matches::assert_matches;
 use crate::Dependencies;
 
 use super::{
-    BuildFlags, BuildInternalClosureError, Config, EnvVariables, Error, InternalLib, Library,
+    pc::{self, PcLibrary},
+    BuildFlags, BuildInternalClosureError, Config, DependencyRequest, EnvVariables, Error,
+    InternalLib, Library, PkgConfigBackend, Probe, ProbeError, Source,
 };
 
 lazy_static! {
@@ -24,10 +26,8 @@ fn create_config(path: &str, env: Vec<(&'static str, &'static str)>) -> Config {
     {
         // PKG_CONFIG_PATH is read by pkg-config, so we need to actually change the env
         let _l = LOCK.lock();
-        env::set_var(
-            "PKG_CONFIG_PATH",
-            env::current_dir().unwrap().join("src").join("tests"),
-        );
+        // Relative, so the `.pc` files tracked by the build flags don't depend on the checkout
+        env::set_var("PKG_CONFIG_PATH", Path::new("src").join("tests"));
     }
 
     let mut hash = HashMap::new();
@@ -43,6 +43,7 @@ fn create_config(path: &str, env: Vec<(&'static str, &'static str)>) -> Config {
     );
 
     hash.insert("CARGO_FEATURE_TEST_FEATURE", "".to_string());
+    hash.insert("OUT_DIR", env::temp_dir().to_string_lossy().to_string());
     env.iter().for_each(|(k, v)| {
         hash.insert(k, v.to_string());
     });
@@ -97,6 +98,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB
@@ -108,8 +119,27 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/testlib.pc
 "#,
     );
 }
@@ -147,15 +177,45 @@ fn version_range_unsatisfied() {
     }
 }
 
+#[test]
+fn version_exact() {
+    let (libraries, _flags) = toml("toml-version-exact", vec![]).unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.version, "1.2.3");
+    let testdata = libraries.get_by_name("testdata").unwrap();
+    assert_eq!(testdata.version, "4.5.6");
+}
+
+#[test]
+fn version_excluded() {
+    // testlib 1.2.3 is excluded so the fallback name is used, testdata 4.5.6 is skipped as optional
+    let (libraries, _flags) = toml("toml-version-excluded", vec![]).unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.name, "testlib-2.0");
+    assert_eq!(testlib.version, "2.0.0");
+    assert!(libraries.get_by_name("testdata").is_none());
+}
+
+#[test]
+fn version_invalid() {
+    toml_err_invalid(
+        "toml-version-invalid",
+        "`package.metadata.system-deps.testlib.version`: invalid version `< 2`",
+    );
+}
+
 fn toml_err(path: &str) -> Error {
     toml(path, vec![]).unwrap_err()
 }
 
 fn toml_err_invalid(path: &str, err_ends_with: &str) {
     let err = toml_err(path);
-    assert_matches!(err, Error::InvalidMetadata(_));
+    let msg = match &err {
+        Error::InvalidMetadata(msg, _) => msg,
+        _ => panic!("Wrong error type: {:?}", err),
+    };
 
-    if !err.to_string().ends_with(err_ends_with) {
+    if !msg.ends_with(err_ends_with) {
         panic!(
             "Expected error to end with: {:?}\nGot error: {:?}",
             err_ends_with, err
@@ -279,6 +339,21 @@ cargo:rustc-link-lib=framework=someframework
 cargo:include=/usr/include/testlib
 cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testlib.pc
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LIB
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_SEARCH_NATIVE
@@ -288,6 +363,7 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LINK_MODIFIERS
 "#,
     );
 }
@@ -307,6 +383,21 @@ cargo:rustc-link-lib=framework=someframework
 cargo:include=/usr/include/testlib
 cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testlib.pc
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LIB
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_SEARCH_NATIVE
@@ -316,6 +407,7 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LINK_MODIFIERS
 "#,
     );
 
@@ -336,6 +428,21 @@ cargo:rustc-link-lib=test
 cargo:include=/usr/include/testlib
 cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTLIB_2.0_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testlib-2.0.pc
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LIB
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_SEARCH_NATIVE
@@ -345,10 +452,204 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TEST_LIB_LINK_MODIFIERS
+"#,
+    );
+}
+
+#[test]
+fn alternatives() {
+    // testlib 1.2.3 doesn't satisfy the first alternative, the second one doesn't exist
+    let (libraries, _flags) = toml("toml-alternatives", vec![]).unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.name, "testlib-3.0");
+    assert_eq!(testlib.version, "3.0.0");
+    assert_eq!(testlib.alternative.as_deref(), Some("testlib-3.0"));
+    // optional and none of its alternatives exist
+    assert!(libraries.get_by_name("testdata").is_none());
+}
+
+#[test]
+fn alternatives_version_override() {
+    toml_err_invalid(
+        "toml-alternatives-version-override",
+        "package.metadata.system-deps.testlib: key v2 cannot be used with `alternatives`",
+    );
+}
+
+#[test]
+fn alternatives_name() {
+    toml_err_invalid(
+        "toml-alternatives-name",
+        "package.metadata.system-deps.testlib: key name cannot be used with `alternatives`",
+    );
+}
+
+#[test]
+fn alternatives_cfg_version() {
+    toml_err_invalid(
+        "toml-alternatives-cfg-version",
+        "package.metadata.system-deps.testlib.cfg(unix): key version cannot be used with `alternatives`",
+    );
+}
+
+#[test]
+fn alternatives_build_internal() {
+    let err = toml(
+        "toml-alternatives",
+        vec![("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "always")],
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "testlib cannot be built internally as it defines alternatives"
+    );
+}
+
+#[test]
+fn check_cfg() {
+    let cfg_flags = |env| {
+        create_config("toml-alternatives", env)
+            .probe_full()
+            .unwrap()
+            .gen_cfg_flags()
+    };
+
+    let flags = cfg_flags(vec![]);
+    assert_flags(
+        flags,
+        r#"cargo::rustc-check-cfg=cfg(system_deps_have_testdata)
+cargo::rustc-check-cfg=cfg(system_deps_testdata_alt_testdata_notexist)
+cargo::rustc-check-cfg=cfg(system_deps_have_testlib)
+cargo::rustc-check-cfg=cfg(system_deps_testlib_alt_testlib)
+cargo::rustc-check-cfg=cfg(system_deps_testlib_alt_testlib_notexist)
+cargo::rustc-check-cfg=cfg(system_deps_testlib_alt_testlib_3_0)
+cargo::rustc-check-cfg=cfg(system_deps_testlib_alt_testlib_2_0)
+cargo:rustc-cfg=system_deps_have_testlib
+cargo:rustc-cfg=system_deps_testlib_alt_testlib_3_0
+"#,
+    );
+
+    let flags = cfg_flags(vec![("CARGO_PKG_RUST_VERSION", "1.80.1")]).to_string();
+    assert!(flags.contains("cargo::rustc-check-cfg=cfg(system_deps_have_testdata)\n"));
+
+    // The crate supports versions of Cargo not knowing the `cargo::` syntax
+    let flags = cfg_flags(vec![("CARGO_PKG_RUST_VERSION", "1.70")]);
+    assert_flags(
+        flags,
+        r#"cargo:rustc-check-cfg=cfg(system_deps_have_testdata)
+cargo:rustc-check-cfg=cfg(system_deps_testdata_alt_testdata_notexist)
+cargo:rustc-check-cfg=cfg(system_deps_have_testlib)
+cargo:rustc-check-cfg=cfg(system_deps_testlib_alt_testlib)
+cargo:rustc-check-cfg=cfg(system_deps_testlib_alt_testlib_notexist)
+cargo:rustc-check-cfg=cfg(system_deps_testlib_alt_testlib_3_0)
+cargo:rustc-check-cfg=cfg(system_deps_testlib_alt_testlib_2_0)
+cargo:rustc-cfg=system_deps_have_testlib
+cargo:rustc-cfg=system_deps_testlib_alt_testlib_3_0
+"#,
+    );
+}
+
+#[test]
+fn version_cfgs() {
+    let flags = create_config("toml-version-cfgs", vec![])
+        .probe_full()
+        .unwrap()
+        .gen_cfg_flags();
+
+    // testdata 4.5.6 and testlib 1.2.3 have been found
+    assert_flags(
+        flags,
+        r#"cargo::rustc-check-cfg=cfg(system_deps_have_testdata)
+cargo::rustc-check-cfg=cfg(system_deps_testdata_version_4_4)
+cargo::rustc-check-cfg=cfg(system_deps_testdata_version_5)
+cargo::rustc-check-cfg=cfg(system_deps_have_testlib)
+cargo::rustc-check-cfg=cfg(system_deps_testlib_version_1_0)
+cargo::rustc-check-cfg=cfg(system_deps_testlib_version_1_2)
+cargo::rustc-check-cfg=cfg(system_deps_testlib_version_1_2_4)
+cargo::rustc-check-cfg=cfg(system_deps_testlib_version_2)
+cargo:rustc-cfg=system_deps_have_testdata
+cargo:rustc-cfg=system_deps_testdata_version_4_4
+cargo:rustc-cfg=system_deps_have_testlib
+cargo:rustc-cfg=system_deps_testlib_version_1_0
+cargo:rustc-cfg=system_deps_testlib_version_1_2
 "#,
     );
 }
 
+#[test]
+fn alternatives_missing_version() {
+    toml_err_invalid(
+        "toml-alternatives-missing-version",
+        "missing key `package.metadata.system-deps.testlib.alternatives[0].version`",
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn check_headers_symbols() {
+    let (libraries, _flags) = toml("toml-check", vec![]).unwrap();
+    assert!(libraries.get_by_name("testcheck").is_some());
+    // optional so skipped as libtest cannot be linked
+    assert!(libraries.get_by_name("testlib").is_none());
+}
+
+#[test]
+#[cfg(unix)]
+fn check_headers_symbols_target_cc() {
+    // The compiler of the target is used when cross-compiling
+    let err = toml(
+        "toml-check",
+        vec![
+            ("TARGET", "aarch64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("SYSTEM_DEPS_SYSROOT", "/"),
+            ("TARGET_CC", "./src/tests/notexist"),
+        ],
+    )
+    .unwrap_err();
+    let msg = match err {
+        Error::CheckFailed(name, msg) if name == "testcheck" => msg,
+        _ => panic!("Wrong error type: {:?}", err),
+    };
+    assert!(msg.contains("notexist"));
+}
+
+#[test]
+#[cfg(unix)]
+fn check_headers_symbols_target_cflags() {
+    // Arguments of the compiler, flags and sysroot of the target are all passed
+    let err = toml(
+        "toml-check",
+        vec![
+            ("TARGET", "aarch64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("SYSTEM_DEPS_SYSROOT", "/"),
+            ("TARGET_CC", "cc -DCHECK_CC_ARG"),
+            ("TARGET_CFLAGS", "-include notexist-cflags.h"),
+        ],
+    )
+    .unwrap_err();
+    let msg = match err {
+        Error::CheckFailed(name, msg) if name == "testcheck" => msg,
+        _ => panic!("Wrong error type: {:?}", err),
+    };
+    assert!(msg.starts_with(
+        "\"cc\" \"-DCHECK_CC_ARG\" \"-include\" \"notexist-cflags.h\" \"--sysroot=/\""
+    ));
+}
+
+#[test]
+#[cfg(unix)]
+fn check_headers_failed() {
+    let err = toml_err("toml-check-failed");
+    let msg = match err {
+        Error::CheckFailed(name, msg) if name == "testcheck_failed" => msg,
+        _ => panic!("Wrong error type: {:?}", err),
+    };
+    assert!(msg.contains("notexist.h"));
+}
+
 #[test]
 fn feature_versions() {
     let (libraries, _) = toml("toml-feature-versions", vec![]).unwrap();
@@ -383,6 +684,22 @@ fn feature_versions() {
     assert_eq!(testlib.name, "testlib-3.0");
 }
 
+#[test]
+fn workspace() {
+    let (libraries, _) = toml("toml-workspace/member", vec![]).unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.name, "testlib");
+    assert_eq!(testlib.version, "1.2.3");
+    // testdata 5 is not available but the member made it optional
+    assert!(libraries.get_by_name("testdata").is_none());
+
+    // Version overrides are inherited from the workspace
+    let (libraries, _) = toml("toml-workspace/member", vec![("CARGO_FEATURE_V2", "")]).unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.name, "testlib-2.0");
+    assert_eq!(testlib.version, "2.0.0");
+}
+
 #[test]
 fn override_search_native() {
     #[cfg(target_os = "windows")]
@@ -416,6 +733,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB
@@ -427,8 +754,27 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/testlib.pc
 "#,
     );
 }
@@ -457,6 +803,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB
@@ -468,8 +824,27 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/testlib.pc
 "#,
     );
 }
@@ -505,6 +880,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB
@@ -516,8 +901,27 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/testlib.pc
 "#,
     );
 }
@@ -546,6 +950,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB
@@ -557,8 +971,27 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/testlib.pc
 "#,
     );
 }
@@ -587,6 +1020,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB
@@ -598,8 +1041,27 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/testlib.pc
 "#,
     );
 }
@@ -633,6 +1095,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB
@@ -644,12 +1116,92 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/testlib.pc
 ",
     );
 }
 
+#[test]
+fn override_targeted() {
+    let host = vec![
+        ("TARGET", "x86_64-unknown-linux-gnu"),
+        ("HOST", "x86_64-unknown-linux-gnu"),
+        ("SYSTEM_DEPS_TESTLIB_SEARCH_NATIVE", "/plain"),
+    ];
+    let link_paths = |env: Vec<(&'static str, &'static str)>| {
+        let (libraries, flags) = toml("toml-good", env).unwrap();
+        let testlib = libraries.get_by_name("testlib").unwrap();
+        (testlib.link_paths.clone(), flags.to_string())
+    };
+
+    let (paths, flags) = link_paths(host.clone());
+    assert_eq!(paths, vec![Path::new("/plain")]);
+    for var in [
+        "SYSTEM_DEPS_TESTLIB_LIB",
+        "SYSTEM_DEPS_TESTLIB_LIB_HOST",
+        "SYSTEM_DEPS_TESTLIB_LIB_x86_64_unknown_linux_gnu",
+        "SYSTEM_DEPS_LINK_HOST",
+        "SYSTEM_DEPS_BUILD_INTERNAL_x86_64_unknown_linux_gnu",
+    ] {
+        assert!(
+            flags.contains(&format!("cargo:rerun-if-env-changed={}\n", var)),
+            "{}",
+            var
+        );
+    }
+
+    let mut env = host.clone();
+    env.push(("SYSTEM_DEPS_TESTLIB_SEARCH_NATIVE_HOST", "/host"));
+    assert_eq!(link_paths(env.clone()).0, vec![Path::new("/host")]);
+
+    env.push((
+        "SYSTEM_DEPS_TESTLIB_SEARCH_NATIVE_x86_64_unknown_linux_gnu",
+        "/target",
+    ));
+    assert_eq!(link_paths(env).0, vec![Path::new("/target")]);
+
+    // Overrides of the host are not used when cross-compiling
+    let (libraries, _) = toml(
+        "toml-good",
+        vec![
+            ("TARGET", "aarch64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG_TARGET", "1"),
+            ("SYSTEM_DEPS_TESTDATA_LIB_TARGET", "data"),
+            ("SYSTEM_DEPS_TESTLIB_NO_PKG_CONFIG_TARGET", "1"),
+            ("SYSTEM_DEPS_TESTLIB_LIB_TARGET", "test"),
+            ("SYSTEM_DEPS_TESTLIB_LIB_HOST", "host"),
+        ],
+    )
+    .unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.source, Source::EnvVariables);
+    assert_eq!(
+        testlib.libs,
+        vec![InternalLib::new("test".to_string(), false)]
+    );
+}
+
 #[test]
 fn override_no_pkg_config() {
     let (libraries, flags) = toml(
@@ -680,6 +1232,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB
@@ -691,134 +1253,602 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
 ",
     );
 }
 
 #[test]
-fn override_no_pkg_config_error() {
+fn override_no_pkg_config_version_header() {
+    let (libraries, _) = toml(
+        "toml-version-header",
+        vec![
+            ("SYSTEM_DEPS_TESTHEADER_NO_PKG_CONFIG", "1"),
+            ("SYSTEM_DEPS_TESTHEADER_LIB", "testheader"),
+            (
+                "SYSTEM_DEPS_TESTHEADER_INCLUDE",
+                "./src/tests/include/testheader",
+            ),
+            ("SYSTEM_DEPS_TESTHEADER_NEW_NO_PKG_CONFIG", "1"),
+            ("SYSTEM_DEPS_TESTHEADER_NEW_LIB", "testheader"),
+            (
+                "SYSTEM_DEPS_TESTHEADER_NEW_INCLUDE",
+                "./src/tests/include/testheader",
+            ),
+        ],
+    )
+    .unwrap();
+    let testheader = libraries.get_by_name("testheader").unwrap();
+    assert_eq!(testheader.version, "2.4.1");
+    // 2.4 is too old but the dependency is optional
+    assert!(libraries.get_by_name("testheader_new").is_none());
+
     let err = toml(
-        "toml-good",
-        vec![("SYSTEM_DEPS_TESTLIB_NO_PKG_CONFIG", "1")],
+        "toml-version-header",
+        vec![
+            ("SYSTEM_DEPS_TESTHEADER_NO_PKG_CONFIG", "1"),
+            ("SYSTEM_DEPS_TESTHEADER_LIB", "testheader"),
+        ],
     )
     .unwrap_err();
-    assert_eq!(
-        err.to_string(),
-        "You should define at least one lib using SYSTEM_DEPS_TESTLIB_LIB or SYSTEM_DEPS_TESTLIB_LIB_FRAMEWORK"
-    );
-}
-
-fn test_build_internal(
-    path: &'static str,
-    env: Vec<(&'static str, &'static str)>,
-    expected_lib: &'static str,
-) -> Result<(Dependencies, bool), (Error, bool)> {
-    let called = Rc::new(Cell::new(false));
-    let called_clone = called.clone();
-    let config = create_config(path, env).add_build_internal(expected_lib, move |lib, version| {
-        called_clone.replace(true);
-        assert_eq!(lib, expected_lib);
-        let mut pkg_lib = pkg_config::Config::new()
-            .print_system_libs(false)
-            .cargo_metadata(false)
-            .probe(lib)
-            .unwrap();
-        pkg_lib.version = version.to_string();
-        Ok(Library::from_pkg_config(lib, pkg_lib))
-    });
-
-    match config.probe_full() {
-        Ok(libraries) => Ok((libraries, called.get())),
-        Err(e) => Err((e, called.get())),
-    }
-}
+    assert_matches!(err, Error::VersionHeader(..));
 
-#[test]
-fn build_internal_always() {
-    let (libraries, called) = test_build_internal(
-        "toml-good",
-        vec![("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "always")],
-        "testlib",
+    // The header is also looked for in the include directories of the compiler
+    let (libraries, _) = toml(
+        "toml-version-header",
+        vec![
+            ("SYSTEM_DEPS_TESTHEADER_NO_PKG_CONFIG", "1"),
+            ("SYSTEM_DEPS_TESTHEADER_LIB", "testheader"),
+            ("CPATH", "./src/tests/include/testheader"),
+            ("SYSTEM_DEPS_TESTHEADER_NEW_NO_PKG_CONFIG", "1"),
+            ("SYSTEM_DEPS_TESTHEADER_NEW_LIB", "testheader"),
+        ],
     )
     .unwrap();
+    let testheader = libraries.get_by_name("testheader").unwrap();
+    assert_eq!(testheader.version, "2.4.1");
+    assert!(libraries.get_by_name("testheader_new").is_none());
 
-    assert!(called);
-    assert!(libraries.get_by_name("testlib").is_some());
-}
-
-#[test]
-fn build_internal_auto_not_called() {
-    // No need to build the lib as the existing version is new enough
-    let (libraries, called) = test_build_internal(
-        "toml-good",
-        vec![("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "auto")],
-        "testlib",
+    let (libraries, _) = toml(
+        "toml-version-header",
+        vec![
+            ("SYSTEM_DEPS_TESTHEADER_NO_PKG_CONFIG", "1"),
+            ("SYSTEM_DEPS_TESTHEADER_LIB", "testheader"),
+            (
+                "SYSTEM_DEPS_TESTHEADER_INCLUDE",
+                "./src/tests/include/testheader",
+            ),
+            ("SYSTEM_DEPS_TESTHEADER_NEW_NO_PKG_CONFIG", "1"),
+            ("SYSTEM_DEPS_TESTHEADER_NEW_LIB", "testheader"),
+        ],
     )
     .unwrap();
-
-    assert!(!called);
-    assert!(libraries.get_by_name("testlib").is_some());
+    assert!(libraries.get_by_name("testheader").is_some());
+    // Optional dependencies whose header cannot be read are skipped
+    assert!(libraries.get_by_name("testheader_new").is_none());
 }
 
+#[cfg(unix)]
 #[test]
-fn build_internal_auto_called() {
-    // Version 5 is not available, so we should try building
-    let (libraries, called) = test_build_internal(
-        "toml-feature-versions",
+fn config_tool() {
+    let (libraries, _) = toml("toml-config-tool", vec![]).unwrap();
+    let testconfig = libraries.get_by_name("testconfig").unwrap();
+    assert_eq!(testconfig.source, Source::ConfigTool);
+    assert_eq!(testconfig.version, "4.5.6");
+    assert_eq!(
+        testconfig.libs,
         vec![
-            ("SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL", "auto"),
-            ("CARGO_FEATURE_V5", ""),
-        ],
-        "testdata",
+            InternalLib::new("testconfig".to_string(), false),
+            InternalLib::new("m".to_string(), false)
+        ]
+    );
+    assert_eq!(
+        testconfig.link_paths,
+        vec![Path::new("/usr/lib/testconfig")]
+    );
+    assert_eq!(
+        testconfig.include_paths,
+        vec![Path::new("/usr/include/testconfig")]
+    );
+    assert_eq!(testconfig.defines.get("TESTCONFIG_ENABLED"), Some(&None));
+    assert_eq!(testconfig.ld_args, vec![vec!["--as-needed"]]);
+    // 4.5.6 is too old but the dependency is optional
+    assert!(libraries.get_by_name("testconfig_old").is_none());
+    // The version is unknown, which is only accepted if explicitly allowed
+    let testconfig = libraries.get_by_name("testconfig_unversioned").unwrap();
+    assert_eq!(testconfig.version, "");
+    assert!(libraries.get_by_name("testconfig_unknown").is_none());
+
+    // Env variables are applied on top of the flags reported by the tool
+    let (libraries, _) = toml(
+        "toml-config-tool",
+        vec![("SYSTEM_DEPS_TESTCONFIG_LIB", "overridden")],
     )
     .unwrap();
-
-    assert!(called);
-    assert!(libraries.get_by_name("testdata").is_some());
+    let testconfig = libraries.get_by_name("testconfig").unwrap();
+    assert_eq!(
+        testconfig.libs,
+        vec![InternalLib::new("overridden".to_string(), false)]
+    );
 }
 
+#[cfg(unix)]
 #[test]
-fn build_internal_auto_never() {
-    // Version 5 is not available, but we forbid to build the lib
-    let (err, called) = test_build_internal(
-        "toml-feature-versions",
-        vec![
-            ("SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL", "never"),
-            ("CARGO_FEATURE_V5", ""),
-        ],
-        "testdata",
-    )
-    .unwrap_err();
-
-    assert!(matches!(err, Error::PkgConfig(..)));
-    assert!(!called);
+fn config_tool_failed() {
+    let err = toml("toml-config-tool-failed", vec![]).unwrap_err();
+    assert_matches!(err, Error::ConfigToolFailed(..));
 }
 
+#[cfg(unix)]
 #[test]
-fn build_internal_always_no_closure() {
-    let config = create_config(
-        "toml-good",
-        vec![("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "always")],
+fn config_tool_unknown_version() {
+    let err = toml("toml-config-tool-unknown-version", vec![]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Failed to get the flags of testconfig from its config tool: version 4 or later is \
+        required but `version` is empty, set `allow-unknown-version = true` to accept an unknown version"
     );
-
-    let err = config.probe_full().unwrap_err();
-    assert!(matches!(err, Error::BuildInternalNoClosure(..)));
 }
 
+#[cfg(unix)]
 #[test]
-fn build_internal_invalid() {
-    let config = create_config(
-        "toml-good",
-        vec![("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "badger")],
+fn cmake() {
+    let (libraries, flags) = toml("toml-cmake", vec![("CMAKE", "./src/tests/testcmake")]).unwrap();
+    let testcmake = libraries.get_by_name("testcmake").unwrap();
+    assert_eq!(testcmake.source, Source::CMake);
+    assert_eq!(testcmake.version, "2.1.0");
+    assert_eq!(
+        testcmake.libs,
+        vec![
+            InternalLib::new("testcmake-gui".to_string(), false),
+            InternalLib::new("testcmake-core".to_string(), false),
+            InternalLib::new("m".to_string(), false)
+        ]
     );
+    assert_eq!(
+        testcmake.include_paths,
+        vec![Path::new("/opt/testcmake/include")]
+    );
+    assert_eq!(testcmake.defines.get("TESTCMAKE_SHARED"), Some(&None));
+    // 2.1.0 is too old but the dependency is optional
+    assert!(libraries.get_by_name("testcmake_new").is_none());
+    assert!(flags
+        .to_string()
+        .contains("cargo:rustc-link-search=native=/opt/testcmake/lib\n"));
 
-    let err = config.probe_full().unwrap_err();
-    assert!(matches!(err, Error::BuildInternalInvalid(..)));
-}
-
-#[test]
+    let err = toml(
+        "toml-cmake-missing",
+        vec![("CMAKE", "./src/tests/testcmake")],
+    )
+    .unwrap_err();
+    assert_matches!(err, Error::CMakeFailed(..));
+
+    // Archives can only be linked statically
+    let (libraries, flags) = toml(
+        "toml-cmake-static",
+        vec![("CMAKE", "./src/tests/testcmake")],
+    )
+    .unwrap();
+    let testcmake = libraries.get_by_name("testcmake").unwrap();
+    assert_eq!(
+        testcmake.libs,
+        vec![InternalLib::new("testcmake-static".to_string(), true)]
+    );
+    assert!(testcmake.statik);
+    assert!(flags
+        .to_string()
+        .contains("cargo:rustc-link-lib=static=testcmake-static\n"));
+
+    // The version of packages not defining it is unknown, which is only accepted if explicitly
+    // allowed
+    let (libraries, _) = toml(
+        "toml-cmake-unversioned",
+        vec![("CMAKE", "./src/tests/testcmake")],
+    )
+    .unwrap();
+    assert_eq!(libraries.get_by_name("testcmake").unwrap().version, "");
+    assert!(libraries.get_by_name("testcmake_unknown").is_none());
+    let err = toml(
+        "toml-cmake-unknown-version",
+        vec![("CMAKE", "./src/tests/testcmake")],
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Failed to find testcmake using CMake: version 1 or later is required but \
+        TestCMakeUnversioned does not define its version, set `allow-unknown-version = true` to \
+        accept an unknown version"
+    );
+
+    // The packages of the host can't be used when cross-compiling
+    let cross = |toolchain_file: Option<&'static str>| {
+        let mut env = vec![
+            ("CMAKE", "./src/tests/testcmake"),
+            ("TARGET", "aarch64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+        ];
+        env.extend(toolchain_file.map(|f| ("CMAKE_TOOLCHAIN_FILE_aarch64-unknown-linux-gnu", f)));
+        toml("toml-cmake", env)
+    };
+    let err = cross(None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Failed to find testcmake using CMake: $CMAKE_TOOLCHAIN_FILE has to be defined when cross-compiling"
+    );
+    let (libraries, _) = cross(Some("./src/tests/testcmake-toolchain.cmake")).unwrap();
+    assert_eq!(
+        libraries.get_by_name("testcmake").unwrap().source,
+        Source::CMake
+    );
+    let err = cross(Some("./src/tests/notexist.cmake")).unwrap_err();
+    assert_matches!(err, Error::CMakeFailed(..));
+
+    // The project is generated in $OUT_DIR
+    let mut config = create_config("toml-cmake", vec![("CMAKE", "./src/tests/testcmake")]);
+    if let EnvVariables::Mock(vars) = &mut config.env {
+        vars.remove("OUT_DIR");
+    }
+    let err = config.probe_full().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Failed to find testcmake using CMake: $OUT_DIR not set"
+    );
+}
+
+#[test]
+fn vcpkg() {
+    let env = |profile| {
+        vec![
+            ("VCPKG_INSTALLED_DIR", "./src/tests/vcpkg"),
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+            ("PROFILE", profile),
+        ]
+    };
+
+    let (libraries, _) = toml("toml-vcpkg", env("release")).unwrap();
+    let testvcpkg = libraries.get_by_name("testvcpkg").unwrap();
+    assert_eq!(testvcpkg.source, Source::Vcpkg);
+    assert_eq!(testvcpkg.version, "1.4.0");
+    // x64-linux is a static triplet
+    assert!(testvcpkg.statik);
+    assert_eq!(
+        testvcpkg.libs,
+        vec![
+            InternalLib::new("testvcpkg".to_string(), true),
+            InternalLib::new("m".to_string(), false)
+        ]
+    );
+    assert!(testvcpkg.include_paths[0].ends_with("include"));
+
+    // Found without .pc file
+    let testlayout = libraries.get_by_name("testlayout").unwrap();
+    assert_eq!(testlayout.source, Source::Vcpkg);
+    assert_eq!(testlayout.version, "3.1");
+    assert_eq!(
+        testlayout.link_paths,
+        vec![Path::new("./src/tests/vcpkg/x64-linux/lib")]
+    );
+    assert_eq!(
+        testlayout.include_paths,
+        vec![Path::new("./src/tests/vcpkg/x64-linux/include")]
+    );
+
+    // Not installed by vcpkg
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.source, Source::PkgConfig);
+
+    let (libraries, _) = toml("toml-vcpkg", env("debug")).unwrap();
+    let testvcpkg = libraries.get_by_name("testvcpkg").unwrap();
+    assert_eq!(testvcpkg.libs[0].name, "testvcpkgd");
+    let testlayout = libraries.get_by_name("testlayout").unwrap();
+    assert_eq!(
+        testlayout.link_paths,
+        vec![Path::new("./src/tests/vcpkg/x64-linux/debug/lib")]
+    );
+}
+
+#[test]
+fn vcpkg_version() {
+    // testvcpkg.pc is too old, so the library files are not used and pkg-config is tried instead
+    let (libraries, flags) = toml(
+        "toml-vcpkg-version",
+        vec![
+            ("VCPKG_INSTALLED_DIR", "./src/tests/vcpkg"),
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+        ],
+    )
+    .unwrap();
+    assert!(libraries.get_by_name("testvcpkg").is_none());
+    assert!(flags.to_string().contains(
+        "cargo:warning=Ignoring testvcpkg installed by vcpkg: Found testvcpkg 1.4.0 but \
+        required version is >= 2\n"
+    ));
+}
+
+#[test]
+fn search() {
+    let (libraries, _) = toml(
+        "toml-search",
+        vec![
+            (
+                "SYSTEM_DEPS_TESTSEARCH_SEARCH_NATIVE",
+                "./src/tests/search/lib",
+            ),
+            (
+                "SYSTEM_DEPS_TESTSEARCH_INCLUDE",
+                "./src/tests/search/include",
+            ),
+        ],
+    )
+    .unwrap();
+    let testsearch = libraries.get_by_name("testsearch").unwrap();
+    assert_eq!(testsearch.source, Source::Search);
+    assert_eq!(testsearch.version, "1.3");
+    assert_eq!(
+        testsearch.libs,
+        vec![InternalLib::new("testsearch".to_string(), true)]
+    );
+    assert_eq!(
+        testsearch.link_paths,
+        vec![Path::new("./src/tests/search/lib")]
+    );
+    assert_eq!(
+        testsearch.include_paths,
+        vec![Path::new("./src/tests/search/include")]
+    );
+
+    let err = toml("toml-search", vec![]).unwrap_err();
+    assert_matches!(err, Error::SearchFailed(..));
+}
+
+#[test]
+fn search_version() {
+    // testlib.pc is too old, so the library files are not looked for as their version is unknown
+    for backend in ["binary", "builtin"] {
+        let err = toml(
+            "toml-search-version",
+            vec![
+                ("PKG_CONFIG_PATH", "src/tests"),
+                ("SYSTEM_DEPS_PKG_CONFIG_BACKEND", backend),
+                (
+                    "SYSTEM_DEPS_TESTLIB_SEARCH_NATIVE",
+                    "./src/tests/search/lib",
+                ),
+            ],
+        )
+        .unwrap_err();
+        assert_matches!(err, Error::PkgConfig(..) | Error::UnsatisfiedVersion(..));
+    }
+
+    // Missing libraries are still looked for
+    let (libraries, _) = toml(
+        "toml-search-version",
+        vec![
+            ("SYSTEM_DEPS_PKG_CONFIG_BACKEND", "builtin"),
+            ("PKG_CONFIG_PATH", "./src/tests/notexist"),
+            (
+                "SYSTEM_DEPS_TESTLIB_SEARCH_NATIVE",
+                "./src/tests/search/lib",
+            ),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        libraries.get_by_name("testlib").unwrap().source,
+        Source::Search
+    );
+}
+
+#[test]
+fn override_no_pkg_config_error() {
+    let err = toml(
+        "toml-good",
+        vec![("SYSTEM_DEPS_TESTLIB_NO_PKG_CONFIG", "1")],
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "You should define at least one lib using SYSTEM_DEPS_TESTLIB_LIB or SYSTEM_DEPS_TESTLIB_LIB_FRAMEWORK"
+    );
+}
+
+fn test_build_internal(
+    path: &'static str,
+    env: Vec<(&'static str, &'static str)>,
+    expected_lib: &'static str,
+) -> Result<(Dependencies, bool), (Error, bool)> {
+    let called = Rc::new(Cell::new(false));
+    let called_clone = called.clone();
+    let config = create_config(path, env).add_build_internal(expected_lib, move |lib, version| {
+        called_clone.replace(true);
+        assert_eq!(lib, expected_lib);
+        let mut pkg_lib = pkg_config::Config::new()
+            .print_system_libs(false)
+            .cargo_metadata(false)
+            .probe(lib)
+            .unwrap();
+        pkg_lib.version = version.to_string();
+        Ok(Library::from_pkg_config(lib, pkg_lib, None))
+    });
+
+    match config.probe_full() {
+        Ok(libraries) => Ok((libraries, called.get())),
+        Err(e) => Err((e, called.get())),
+    }
+}
+
+fn build_internal_pkg_config(
+    backend: &'static str,
+    version: &'static str,
+) -> Result<Dependencies, Error> {
+    create_config(
+        "toml-good",
+        vec![
+            ("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "always"),
+            ("SYSTEM_DEPS_PKG_CONFIG_BACKEND", backend),
+            ("PKG_CONFIG_PATH", "src/tests"),
+        ],
+    )
+    .add_build_internal("testlib", move |lib, _| {
+        let dir = env::current_dir().unwrap().join("src").join("tests");
+        Library::from_internal_pkg_config(dir, lib, version)
+    })
+    .probe_full()
+}
+
+#[test]
+fn build_internal_from_pkg_config() {
+    // The backend is the one of the `Config` calling the closure
+    for backend in ["binary", "builtin"] {
+        let libraries = build_internal_pkg_config(backend, "1.2").unwrap();
+        let testlib = libraries.get_by_name("testlib").unwrap();
+        assert_eq!(testlib.version, "1.2.3");
+
+        let err = build_internal_pkg_config(backend, "5").unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                Error::BuildInternalClosureError(_, BuildInternalClosureError::Error(e))
+                    if matches!(e.as_ref(), Error::UnsatisfiedVersion(..))
+            ),
+            "{:?}",
+            err
+        );
+
+        let err = build_internal_pkg_config(backend, "a.b").unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                Error::BuildInternalClosureError(_, BuildInternalClosureError::Error(e))
+                    if matches!(e.as_ref(), Error::InvalidMetadata(..))
+            ),
+            "{:?}",
+            err
+        );
+    }
+}
+
+#[test]
+fn build_internal_always() {
+    let (libraries, called) = test_build_internal(
+        "toml-good",
+        vec![("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "always")],
+        "testlib",
+    )
+    .unwrap();
+
+    assert!(called);
+    assert!(libraries.get_by_name("testlib").is_some());
+}
+
+#[test]
+fn build_internal_auto_not_called() {
+    // No need to build the lib as the existing version is new enough
+    let (libraries, called) = test_build_internal(
+        "toml-good",
+        vec![("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "auto")],
+        "testlib",
+    )
+    .unwrap();
+
+    assert!(!called);
+    assert!(libraries.get_by_name("testlib").is_some());
+}
+
+#[test]
+fn build_internal_auto_called() {
+    // Version 5 is not available, so we should try building
+    let (libraries, called) = test_build_internal(
+        "toml-feature-versions",
+        vec![
+            ("SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL", "auto"),
+            ("CARGO_FEATURE_V5", ""),
+        ],
+        "testdata",
+    )
+    .unwrap();
+
+    assert!(called);
+    assert!(libraries.get_by_name("testdata").is_some());
+}
+
+#[test]
+fn build_internal_auto_never() {
+    // Version 5 is not available, but we forbid to build the lib
+    let (err, called) = test_build_internal(
+        "toml-feature-versions",
+        vec![
+            ("SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL", "never"),
+            ("CARGO_FEATURE_V5", ""),
+        ],
+        "testdata",
+    )
+    .unwrap_err();
+
+    assert!(matches!(err, Error::PkgConfig(..)));
+    assert!(!called);
+}
+
+#[test]
+fn build_internal_always_no_closure() {
+    let config = create_config(
+        "toml-good",
+        vec![("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "always")],
+    );
+
+    let err = config.probe_full().unwrap_err();
+    assert!(matches!(err, Error::BuildInternalNoClosure(..)));
+}
+
+#[test]
+fn build_internal_metadata() {
+    let (libraries, called) =
+        test_build_internal("toml-build-internal", vec![], "testlib").unwrap();
+    assert!(called);
+    assert!(libraries.get_by_name("testlib").is_some());
+
+    // env variables take precedence over the metadata
+    for var in [
+        "SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL",
+        "SYSTEM_DEPS_BUILD_INTERNAL",
+    ] {
+        let (libraries, called) =
+            test_build_internal("toml-build-internal", vec![(var, "never")], "testlib").unwrap();
+        assert!(!called);
+        assert!(libraries.get_by_name("testlib").is_some());
+    }
+}
+
+#[test]
+fn build_internal_invalid() {
+    let config = create_config(
+        "toml-good",
+        vec![("SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL", "badger")],
+    );
+
+    let err = config.probe_full().unwrap_err();
+    assert!(matches!(err, Error::BuildInternalInvalid(..)));
+}
+
+#[test]
 fn build_internal_wrong_version() {
     // Require version 5
     let called = Rc::new(Cell::new(false));
@@ -838,7 +1868,7 @@ fn build_internal_wrong_version() {
             .cargo_metadata(false)
             .probe(lib)
             .unwrap();
-        Ok(Library::from_pkg_config(lib, pkg_lib))
+        Ok(Library::from_pkg_config(lib, pkg_lib, None))
     });
 
     let err = config.probe_full().unwrap_err();
@@ -880,7 +1910,7 @@ fn build_internal_always_global() {
                 .probe(lib)
                 .unwrap();
             pkg_lib.version = version.to_string();
-            Ok(Library::from_pkg_config(lib, pkg_lib))
+            Ok(Library::from_pkg_config(lib, pkg_lib, None))
         })
         .add_build_internal("testdata", move |lib, version| {
             let (a, _) = called_clone2.get();
@@ -891,7 +1921,7 @@ fn build_internal_always_global() {
                 .probe(lib)
                 .unwrap();
             pkg_lib.version = version.to_string();
-            Ok(Library::from_pkg_config(lib, pkg_lib))
+            Ok(Library::from_pkg_config(lib, pkg_lib, None))
         });
 
     let libraries = config.probe_full().unwrap();
@@ -922,7 +1952,7 @@ fn build_internal_global_override() {
             .probe(lib)
             .unwrap();
         pkg_lib.version = version.to_string();
-        Ok(Library::from_pkg_config(lib, pkg_lib))
+        Ok(Library::from_pkg_config(lib, pkg_lib, None))
     })
     .add_build_internal("testdata", move |lib, version| {
         let (a, _) = called_clone2.get();
@@ -933,7 +1963,7 @@ fn build_internal_global_override() {
             .probe(lib)
             .unwrap();
         pkg_lib.version = version.to_string();
-        Ok(Library::from_pkg_config(lib, pkg_lib))
+        Ok(Library::from_pkg_config(lib, pkg_lib, None))
     });
 
     let libraries = config.probe_full().unwrap();
@@ -1005,40 +2035,138 @@ fn aggregate() {
     );
 }
 
-#[test]
-fn os_specific() {
+#[test]
+fn os_specific() {
+    let (libraries, _) = toml(
+        "toml-os-specific",
+        vec![("TARGET", "x86_64-alpine-linux-musl")],
+    )
+    .unwrap();
+    assert!(libraries.get_by_name("testdata").is_some());
+    assert!(libraries.get_by_name("testlib").is_some());
+    assert!(libraries.get_by_name("testanotherlib").is_some());
+
+    let (libraries, _) = toml("toml-os-specific", vec![("TARGET", "x86_64-apple-darwin")]).unwrap();
+    assert!(libraries.get_by_name("testdata").is_none());
+    assert!(libraries.get_by_name("testlib").is_none());
+    assert!(libraries.get_by_name("testanotherlib").is_some());
+
+    let (libraries, _) = toml(
+        "toml-os-specific",
+        vec![("TARGET", "x86_64-pc-windows-gnu")],
+    )
+    .unwrap();
+    assert!(libraries.get_by_name("testdata").is_none());
+    assert!(libraries.get_by_name("testlib").is_some());
+    assert!(libraries.get_by_name("testanotherlib").is_none());
+}
+
+#[test]
+fn custom_cfg() {
+    let (libraries, _) = toml(
+        "toml-custom-cfg",
+        vec![("TARGET", "x86_64-unknown-linux-gnu")],
+    )
+    .unwrap();
+    assert!(libraries.get_by_name("testanotherlib").is_none());
+
+    // Custom cfg passed with `--cfg badger`
+    let (libraries, _) = toml(
+        "toml-custom-cfg",
+        vec![
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+            ("CARGO_CFG_BADGER", ""),
+        ],
+    )
+    .unwrap();
+    assert!(libraries.get_by_name("testanotherlib").is_some());
+}
+
+#[test]
+fn cargo_cfg() {
+    let (libraries, _) = toml("toml-cargo-cfg", vec![]).unwrap();
+    assert!(libraries.get_by_name("testlib").is_some());
+    assert!(libraries.get_by_name("testdata").is_none());
+    assert!(libraries.get_by_name("testanotherlib").is_none());
+
+    let (libraries, _) = toml(
+        "toml-cargo-cfg",
+        vec![
+            ("CARGO_CFG_TARGET_FEATURE", "fxsr,crt-static,sse"),
+            ("CARGO_CFG_PANIC", "abort"),
+            ("CARGO_CFG_DEBUG_ASSERTIONS", ""),
+        ],
+    )
+    .unwrap();
+    assert!(libraries.get_by_name("testlib").is_some());
+    assert!(libraries.get_by_name("testdata").is_some());
+    assert!(libraries.get_by_name("testanotherlib").is_some());
+
+    // CARGO_CFG_* variables take precedence over the target triple
+    let (libraries, _) = toml(
+        "toml-os-specific",
+        vec![
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+            ("CARGO_CFG_TARGET_OS", "macos"),
+            ("CARGO_CFG_TARGET_FAMILY", "unix"),
+        ],
+    )
+    .unwrap();
+    assert!(libraries.get_by_name("testdata").is_none());
+    assert!(libraries.get_by_name("testlib").is_none());
+    assert!(libraries.get_by_name("testanotherlib").is_some());
+
+    // Custom target without TARGET triple
     let (libraries, _) = toml(
         "toml-os-specific",
-        vec![("TARGET", "x86_64-alpine-linux-musl")],
+        vec![
+            ("CARGO_CFG_TARGET_OS", "linux"),
+            ("CARGO_CFG_TARGET_FAMILY", "unix"),
+        ],
     )
     .unwrap();
     assert!(libraries.get_by_name("testdata").is_some());
     assert!(libraries.get_by_name("testlib").is_some());
     assert!(libraries.get_by_name("testanotherlib").is_some());
+}
 
-    let (libraries, _) = toml("toml-os-specific", vec![("TARGET", "x86_64-apple-darwin")]).unwrap();
-    assert!(libraries.get_by_name("testdata").is_none());
-    assert!(libraries.get_by_name("testlib").is_none());
+#[test]
+fn cfg_override() {
+    let (libraries, _) = toml(
+        "toml-cfg-override",
+        vec![("TARGET", "x86_64-unknown-linux-gnu")],
+    )
+    .unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.name, "testlib-2.0");
+    assert_eq!(testlib.version, "2.0.0");
+    assert!(libraries.get_by_name("testdata").is_some());
     assert!(libraries.get_by_name("testanotherlib").is_some());
 
     let (libraries, _) = toml(
-        "toml-os-specific",
-        vec![("TARGET", "x86_64-pc-windows-gnu")],
+        "toml-cfg-override",
+        vec![("TARGET", "aarch64-apple-darwin")],
     )
     .unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.name, "testlib");
+    assert_eq!(testlib.version, "1.2.3");
     assert!(libraries.get_by_name("testdata").is_none());
-    assert!(libraries.get_by_name("testlib").is_some());
     assert!(libraries.get_by_name("testanotherlib").is_none());
-}
 
-#[test]
-fn invalid_cfg() {
-    let err = toml(
-        "toml-invalid-cfg",
-        vec![("TARGET", "x86_64-unknown-linux-gnu")],
+    // Overridden name doesn't exist but the dependency is now optional
+    let (libraries, _) = toml(
+        "toml-cfg-override",
+        vec![("TARGET", "x86_64-pc-windows-msvc")],
     )
-    .unwrap_err();
+    .unwrap();
+    assert!(libraries.get_by_name("testlib").is_none());
+}
 
+#[test]
+fn unsupported_cfg() {
+    // Neither TARGET nor CARGO_CFG_TARGET_OS are defined
+    let err = toml("toml-os-specific", vec![]).unwrap_err();
     assert_matches!(err, Error::UnsupportedCfg(_));
 }
 
@@ -1065,6 +2193,23 @@ cargo:rustc-link-lib=framework=someframework
 cargo:include=./src/tests/include/testlib
 cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTSTATICLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/teststaticlib.pc
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LIB
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_SEARCH_NATIVE
@@ -1074,15 +2219,27 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 "#
         .to_string()
         .as_str(),
@@ -1121,6 +2278,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LIB
@@ -1132,12 +2299,223 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
 ",
     );
 }
 
+#[test]
+fn static_metadata() {
+    let (libraries, flags) = toml("toml-link", vec![]).unwrap();
+    assert!(libraries.get_by_name("testdata").unwrap().statik);
+    // libtest.a is not available
+    assert!(!libraries.get_by_name("testlib").unwrap().statik);
+    assert!(libraries.get_by_name("teststaticlib").unwrap().statik);
+
+    let flags = flags.to_string();
+    assert!(flags.contains("cargo:rustc-link-lib=test\n"));
+    assert!(flags.contains("cargo:rustc-link-lib=static=teststatic\n"));
+
+    // env variables take precedence over the metadata
+    let (libraries, _) = toml(
+        "toml-link",
+        vec![
+            ("SYSTEM_DEPS_TESTDATA_LINK", "dynamic"),
+            ("SYSTEM_DEPS_TESTLIB_LINK", "static"),
+        ],
+    )
+    .unwrap();
+    assert!(!libraries.get_by_name("testdata").unwrap().statik);
+    assert!(libraries.get_by_name("testlib").unwrap().statik);
+    assert!(libraries.get_by_name("teststaticlib").unwrap().statik);
+
+    // either variable can request static linking
+    let (libraries, _) = toml(
+        "toml-link",
+        vec![
+            ("SYSTEM_DEPS_TESTSTATICLIB_LINK", "dynamic"),
+            ("SYSTEM_DEPS_LINK", "static"),
+        ],
+    )
+    .unwrap();
+    assert!(libraries.get_by_name("testdata").unwrap().statik);
+    assert!(libraries.get_by_name("testlib").unwrap().statik);
+    assert!(libraries.get_by_name("teststaticlib").unwrap().statik);
+
+    let err = toml("toml-link", vec![("SYSTEM_DEPS_LINK", "statc")]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid value in SYSTEM_DEPS_LINK: statc (allowed: 'static', 'dynamic', 'prefer-static')"
+    );
+}
+
+#[test]
+fn static_prefer_static() {
+    // libtestrequires.a is not available, so it is probed again without its private libraries
+    let (libraries, flags) = toml("toml-link-prefer-static", vec![]).unwrap();
+    assert!(!libraries.get_by_name("testrequires").unwrap().statik);
+    let flags = flags.to_string();
+    assert!(flags.contains("cargo:rustc-link-lib=testrequires\n"));
+    assert!(!flags.contains("cargo:rustc-link-lib=m\n"));
+
+    // the libraries defined using env variables are unknown when probing
+    let (libraries, flags) = toml(
+        "toml-link-prefer-static",
+        vec![
+            ("SYSTEM_DEPS_TESTREQUIRES_NO_PKG_CONFIG", "1"),
+            ("SYSTEM_DEPS_TESTREQUIRES_LIB", "testrequires"),
+        ],
+    )
+    .unwrap();
+    assert!(!libraries.get_by_name("testrequires").unwrap().statik);
+    assert!(flags
+        .to_string()
+        .contains("cargo:rustc-link-lib=testrequires\n"));
+}
+
+// Provides `testmock` 2.1
+struct MockProbe;
+
+impl Probe for MockProbe {
+    fn probe(&self, dep: &DependencyRequest) -> Result<Option<Library>, ProbeError> {
+        match dep.name {
+            "testmock" => {
+                let mut lib = Library::from_env_variables("testmock");
+                lib.libs = vec![InternalLib::new("testmock".to_string(), false)];
+                lib.version = "2.1".to_string();
+                Ok(Some(lib))
+            }
+            "testmissing" => Err(ProbeError::failed("mock failure")),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[test]
+fn sources() {
+    let err = create_config("toml-sources", vec![])
+        .add_probe("mock", MockProbe)
+        .probe_full()
+        .unwrap_err();
+    let errors = match err {
+        Error::SourcesFailed(name, errors) if name == "testmissing" => errors,
+        e => panic!("Wrong error: {:?}", e),
+    };
+    let sources = errors
+        .iter()
+        .map(|(s, _)| s.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sources,
+        vec!["env", "custom:mock", "custom:unknown", "internal"]
+    );
+    assert_matches!(errors[0].1, Error::MissingLib(..));
+    assert_matches!(errors[1].1, Error::Probe(..));
+    assert_matches!(errors[2].1, Error::InvalidMetadata(..));
+    assert_matches!(errors[3].1, Error::BuildInternalNoClosure(..));
+
+    let libraries = create_config("toml-sources", vec![("SYSTEM_DEPS_TESTDATA_LIB", "custom")])
+        .add_probe("mock", MockProbe)
+        .add_build_internal("testmissing", |lib, version| {
+            let mut lib = Library::from_env_variables(lib);
+            lib.libs = vec![InternalLib::new("testmissing".to_string(), false)];
+            lib.version = version.to_string();
+            Ok(lib)
+        })
+        .probe_full()
+        .unwrap();
+
+    // Defined in the env, before pkg-config
+    let testdata = libraries.get_by_name("testdata").unwrap();
+    assert_eq!(testdata.source, Source::EnvVariables);
+    // Not found by the mock probe
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.source, Source::PkgConfig);
+    // Not found by pkg-config
+    let testmock = libraries.get_by_name("testmock").unwrap();
+    assert_eq!(testmock.version, "2.1");
+    let testmissing = libraries.get_by_name("testmissing").unwrap();
+    assert_eq!(testmissing.version, "1");
+}
+
+#[test]
+fn sources_invalid() {
+    toml_err_invalid(
+        "toml-sources-invalid",
+        "`package.metadata.system-deps.testlib.sources[1]`: Unknown variant: `custom:`",
+    );
+}
+
+#[test]
+fn link_invalid() {
+    toml_err_invalid(
+        "toml-link-invalid",
+        "`package.metadata.system-deps.testlib.link`: Unknown variant: `shared`",
+    );
+}
+
+#[test]
+fn link_modifiers() {
+    let (_, flags) = toml("toml-link-modifiers", vec![]).unwrap();
+    let flags = flags.to_string();
+    // libtest.a is not available so `+whole-archive` is dropped
+    assert!(flags.contains("cargo:rustc-link-lib=dylib:+verbatim,-as-needed=test\n"));
+    // `+as-needed` only applies to dynamic libraries
+    assert!(flags.contains("cargo:rustc-link-lib=static:+whole-archive,-bundle=foo:teststatic\n"));
+
+    // env variables take precedence over the metadata
+    let (_, flags) = toml(
+        "toml-link-modifiers",
+        vec![
+            ("SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS", "=foo:test"),
+            (
+                "SYSTEM_DEPS_TESTSTATICLIB_LINK_MODIFIERS",
+                "-bundle +bundle,+verbatim=teststatic",
+            ),
+        ],
+    )
+    .unwrap();
+    let flags = flags.to_string();
+    assert!(flags.contains("cargo:rustc-link-lib=foo:test\n"));
+    assert!(flags.contains("cargo:rustc-link-lib=static:+bundle,+verbatim=teststatic\n"));
+
+    let err = toml(
+        "toml-link-modifiers",
+        vec![("SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS", "+as_needed")],
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid value in SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS: Unknown variant: `+as_needed`"
+    );
+}
+
+#[test]
+fn link_modifiers_invalid() {
+    toml_err_invalid(
+        "toml-link-modifiers-invalid",
+        "`package.metadata.system-deps.testlib.link-modifiers.test[0]`: Unknown variant: `+whole_archive`",
+    );
+}
+
 #[test]
 fn static_all_libs() {
     let (libraries, flags) = toml("toml-static", vec![("SYSTEM_DEPS_LINK", "static")]).unwrap();
@@ -1157,6 +2535,23 @@ cargo:rustc-link-lib=framework=someframework
 cargo:include=./src/tests/include/testlib
 cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTSTATICLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/teststaticlib.pc
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LIB
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_SEARCH_NATIVE
@@ -1166,15 +2561,27 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTSTATICLIB_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 "#,
     );
 }
@@ -1200,15 +2607,43 @@ cargo:rustc-link-lib=framework=someframework
 cargo:include=/usr/include/testlib
 cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/testlib.pc
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_SEARCH_NATIVE
@@ -1218,6 +2653,7 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIB_LINK_MODIFIERS
 "#,
     );
 }
@@ -1254,6 +2690,16 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LIB_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_NO_PKG_CONFIG
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_FRAMEWORK
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_SEARCH_NATIVE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_BUILD_INTERNAL
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_INCLUDE
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LDFLAGS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LIB_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_LINK_MODIFIERS
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_FRAMEWORK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTMORE_SEARCH_NATIVE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIBWITHRPATH_INCLUDE
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIBWITHRPATH_LDFLAGS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIBWITHRPATH_LIB
@@ -1265,8 +2711,504 @@ cargo:rerun-if-env-changed=SYSTEM_DEPS_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIBWITHRPATH_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_BUILD_INTERNAL
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTDATA_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIBWITHRPATH_LINK
+cargo:rerun-if-env-changed=SYSTEM_DEPS_TESTLIBWITHRPATH_LINK_MODIFIERS
 cargo:rerun-if-env-changed=SYSTEM_DEPS_LINK
+cargo:rerun-if-env-changed=TESTDATA_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=TESTLIBWITHRPATH_NO_PKG_CONFIG
+cargo:rerun-if-env-changed=SYSTEM_DEPS_PKG_CONFIG_BACKEND
+cargo:rerun-if-env-changed=VCPKG_ROOT
+cargo:rerun-if-env-changed=VCPKG_INSTALLED_DIR
+cargo:rerun-if-env-changed=VCPKG_DEFAULT_TRIPLET
+cargo:rerun-if-env-changed=SYSROOT
+cargo:rerun-if-env-changed=SYSTEM_DEPS_SYSROOT
+cargo:rerun-if-env-changed=PKG_CONFIG
+cargo:rerun-if-env-changed=PKG_CONFIG_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_LIBDIR
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSROOT_DIR
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_CROSS
+cargo:rerun-if-env-changed=PKG_CONFIG_SYSTEM_LIBRARY_PATH
+cargo:rerun-if-env-changed=PKG_CONFIG_ALLOW_SYSTEM_LIBS
+cargo:rerun-if-changed=src/tests/testdata.pc
+cargo:rerun-if-changed=src/tests/testlibwithrpath.pc
 "#,
     );
 }
+
+#[test]
+fn builtin_pkg_config_same_as_binary() {
+    let config = create_config("toml-good", vec![("PKG_CONFIG_PATH", "src/tests")]);
+
+    for entry in std::fs::read_dir("src/tests").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("pc") {
+            continue;
+        }
+        // Flags of required packages are ordered differently by the pkg-config implementations
+        if std::fs::read_to_string(&path).unwrap().contains("Requires") {
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_str().unwrap();
+
+        for statik in [false, true] {
+            let mut binary = pkg_config::Config::new();
+            binary
+                .print_system_libs(false)
+                .cargo_metadata(false)
+                .statik(statik);
+            let binary = PcLibrary::from(binary.probe(name).unwrap());
+
+            let mut builtin = pc::Resolver::new(&config.env);
+            builtin.statik(statik);
+            let builtin = builtin.probe(name).unwrap();
+
+            assert_eq!(binary, builtin, "{} static: {}", name, statik);
+        }
+    }
+}
+
+#[test]
+fn builtin_pkg_config_requires() {
+    let config = create_config("toml-good", vec![("PKG_CONFIG_PATH", "src/tests")]);
+    let mut resolver = pc::Resolver::new(&config.env);
+
+    let lib = resolver.probe("testrequires").unwrap();
+    assert_eq!(lib.version, "0.5");
+    assert_eq!(
+        lib.include_paths,
+        vec![
+            PathBuf::from("./src/tests/include/testrequires"),
+            PathBuf::from("/usr/include/testlib"),
+            PathBuf::from("/usr/include/testanotherlib"),
+        ]
+    );
+    assert_eq!(lib.defines.len(), 4);
+    assert_eq!(lib.libs, vec!["testrequires", "test"]);
+    assert_eq!(lib.frameworks, vec!["someframework"]);
+
+    let lib = resolver.statik(true).probe("testrequires").unwrap();
+    assert_eq!(lib.libs, vec!["testrequires", "m", "test", "test", "test2"]);
+    assert_eq!(
+        lib.frameworks,
+        vec!["someframework", "someframework", "someotherframework"]
+    );
+
+    let config = create_config(
+        "toml-good",
+        vec![
+            ("PKG_CONFIG_PATH", "src/tests"),
+            ("PKG_CONFIG_SYSROOT_DIR", "/sysroot"),
+        ],
+    );
+    let lib = pc::Resolver::new(&config.env)
+        .probe("testrequires")
+        .unwrap();
+    assert_eq!(
+        lib.include_paths,
+        vec![
+            PathBuf::from("./src/tests/include/testrequires"),
+            PathBuf::from("/sysroot/usr/include/testlib"),
+            PathBuf::from("/sysroot/usr/include/testanotherlib"),
+        ]
+    );
+    assert_eq!(
+        lib.link_paths,
+        vec![
+            PathBuf::from("./src/tests/lib"),
+            PathBuf::from("/sysroot/usr/lib/")
+        ]
+    );
+}
+
+#[test]
+fn builtin_pkg_config_backend() {
+    let (libraries, flags) = toml(
+        "toml-good",
+        vec![
+            ("PKG_CONFIG_PATH", "src/tests"),
+            ("SYSTEM_DEPS_PKG_CONFIG_BACKEND", "builtin"),
+        ],
+    )
+    .unwrap();
+    let (_, binary_flags) = toml("toml-good", vec![("PKG_CONFIG_PATH", "src/tests")]).unwrap();
+    assert_flags(flags, &binary_flags.to_string());
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.version, "1.2.3");
+
+    let libraries = create_config(
+        "toml-version-excluded",
+        vec![("PKG_CONFIG_PATH", "src/tests")],
+    )
+    .pkg_config_backend(PkgConfigBackend::Builtin)
+    .probe_full()
+    .unwrap();
+    assert_eq!(
+        libraries.get_by_name("testlib").unwrap().name,
+        "testlib-2.0"
+    );
+
+    let err = toml(
+        "toml-good",
+        vec![("SYSTEM_DEPS_PKG_CONFIG_BACKEND", "pkgconf")],
+    )
+    .unwrap_err();
+    assert_matches!(err, Error::PkgConfigBackendInvalid(_));
+}
+
+#[test]
+fn cross_compilation() {
+    let env = |vars: Vec<(&'static str, &'static str)>| {
+        let mut env = vec![
+            ("TARGET", "aarch64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("PKG_CONFIG_PATH_aarch64_unknown_linux_gnu", "src/tests"),
+        ];
+        env.extend(vars);
+        env
+    };
+
+    let err = toml("toml-good", env(vec![])).unwrap_err();
+    assert_matches!(err, Error::CrossCompilationNoSysroot(ref t) if t == "aarch64-unknown-linux-gnu");
+    assert!(err
+        .to_string()
+        .contains("SYSTEM_DEPS_SYSROOT_AARCH64_UNKNOWN_LINUX_GNU"));
+
+    let sysroot = ("SYSTEM_DEPS_SYSROOT_AARCH64_UNKNOWN_LINUX_GNU", "/sysroot");
+    for backend in ["builtin", "binary"] {
+        let (libraries, _) = toml(
+            "toml-good",
+            env(vec![sysroot, ("SYSTEM_DEPS_PKG_CONFIG_BACKEND", backend)]),
+        )
+        .unwrap();
+        let testlib = libraries.get_by_name("testlib").unwrap();
+        assert_eq!(testlib.version, "1.2.3");
+        assert_eq!(
+            testlib.include_paths,
+            vec![PathBuf::from("/sysroot/usr/include/testlib")],
+            "{}",
+            backend
+        );
+        assert_eq!(
+            testlib.link_paths,
+            vec![PathBuf::from("/sysroot/usr/lib/")],
+            "{}",
+            backend
+        );
+    }
+
+    // Use pkg-config as is
+    let (libraries, _) = toml(
+        "toml-good",
+        env(vec![
+            ("PKG_CONFIG_ALLOW_CROSS", "1"),
+            ("SYSTEM_DEPS_PKG_CONFIG_BACKEND", "builtin"),
+        ]),
+    )
+    .unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(
+        testlib.include_paths,
+        vec![PathBuf::from("/usr/include/testlib")]
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn cross_compilation_prefixed_pkg_config() {
+    // A triple-prefixed pkg-config in PATH is configured for the target, no sysroot is needed
+    let (libraries, _) = toml(
+        "toml-good",
+        vec![
+            ("TARGET", "aarch64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("PKG_CONFIG_PATH_aarch64_unknown_linux_gnu", "src/tests"),
+            ("PATH", "./src/tests/bin"),
+            ("SYSTEM_DEPS_PKG_CONFIG_BACKEND", "binary"),
+        ],
+    )
+    .unwrap();
+    let testlib = libraries.get_by_name("testlib").unwrap();
+    assert_eq!(testlib.version, "1.2.3");
+    assert_eq!(
+        testlib.include_paths,
+        vec![PathBuf::from("/usr/include/testlib")]
+    );
+}
+
+// The DEP_$LINKS_* variables defined by Cargo for the crates depending on one which found `libraries`
+fn dep_env_vars(links: &str, libraries: &Dependencies) -> Vec<(&'static str, &'static str)> {
+    let flags = libraries.gen_metadata_flags().to_string();
+    flags
+        .lines()
+        .map(|line| {
+            let (key, value) = line
+                .strip_prefix("cargo::metadata=")
+                .or_else(|| line.strip_prefix("cargo:"))
+                .unwrap()
+                .split_once('=')
+                .unwrap();
+            let key: &'static str = Box::leak(format!("DEP_{}_{}", links, key).into_boxed_str());
+            let value: &'static str = Box::leak(value.to_string().into_boxed_str());
+            (key, value)
+        })
+        .collect()
+}
+
+#[test]
+fn dep_env() {
+    let libraries = create_config(
+        "toml-good",
+        vec![
+            ("CARGO_PKG_RUST_VERSION", "1.70"),
+            ("CARGO_MANIFEST_LINKS", "test"),
+            ("PKG_CONFIG_PATH", "src/tests"),
+        ],
+    )
+    .probe_full()
+    .unwrap();
+    let flags = libraries.gen_metadata_flags().to_string();
+    assert!(flags.contains("cargo:SYSTEM_DEPS=testdata testlib\n"));
+    assert!(flags.contains("cargo:SYSTEM_DEPS_TESTLIB_VERSION=1.2.3\n"));
+    assert!(flags.contains("cargo:SYSTEM_DEPS_TESTLIB_DEFINES=AWESOME BADGER=yes\n"));
+
+    let env = dep_env_vars("TEST", &libraries).into_iter();
+    let env = EnvVariables::Mock(env.map(|(k, v)| (k, v.to_string())).collect());
+    let exported = Dependencies::from_dep_env_with(&env, "test").unwrap();
+
+    assert_eq!(exported.iter().len(), 2);
+    for (name, lib) in libraries.iter() {
+        let e = exported.get_by_name(name).unwrap();
+        assert_eq!(e.name, lib.name);
+        assert_eq!(e.version, lib.version);
+        assert_eq!(e.source, lib.source);
+        assert_eq!(e.statik, lib.statik);
+        assert_eq!(e.libs, lib.libs);
+        assert_eq!(e.link_paths, lib.link_paths);
+        assert_eq!(e.frameworks, lib.frameworks);
+        assert_eq!(e.framework_paths, lib.framework_paths);
+        assert_eq!(e.include_paths, lib.include_paths);
+        assert_eq!(e.ld_args, lib.ld_args);
+        assert_eq!(e.defines, lib.defines);
+        assert_eq!(e.alternative, lib.alternative);
+        assert_eq!(e.pc_file, lib.pc_file);
+    }
+    assert_eq!(
+        exported.get_by_name("testlib").unwrap().pc_file,
+        Some(Path::new("src/tests/testlib.pc").into())
+    );
+
+    // Cargo only uppercases `links` and replaces dashes
+    let env = dep_env_vars("TEST_SYS", &libraries).into_iter();
+    let env = EnvVariables::Mock(env.map(|(k, v)| (k, v.to_string())).collect());
+    let exported = Dependencies::from_dep_env_with(&env, "test-sys").unwrap();
+    assert_eq!(exported.iter().len(), 2);
+
+    let err =
+        Dependencies::from_dep_env_with(&EnvVariables::Mock(HashMap::new()), "test").unwrap_err();
+    assert_matches!(err, Error::DepEnv(_));
+    assert_eq!(
+        err.to_string(),
+        "DEP_TEST_SYSTEM_DEPS is not defined, the crate with `links = \"test\"` has to use system-deps"
+    );
+}
+
+#[test]
+fn dep_env_escaped() {
+    // Separators and backslashes within the items survive the round trip
+    let mut lib = Library::from_env_variables("testlib");
+    lib.libs = vec![InternalLib::new("test lib".to_string(), true)];
+    lib.frameworks = vec!["Test Framework".to_string()];
+    lib.defines
+        .insert("GREETING".to_string(), Some("\"hello world\"".to_string()));
+    lib.defines
+        .insert("SEPARATOR".to_string(), Some("\\".to_string()));
+    lib.ld_args = vec![
+        vec!["-rpath".to_string(), "/opt/a,b c".to_string()],
+        vec!["--as-needed".to_string()],
+    ];
+
+    let vars = lib.to_dep_env().into_iter().collect::<HashMap<_, _>>();
+    assert_eq!(vars["DEFINES"], "GREETING=\"hello\\ world\" SEPARATOR=\\\\");
+    assert_eq!(vars["LDFLAGS"], "-rpath,/opt/a\\,b\\ c --as-needed");
+
+    let exported = Library::from_dep_env(|key| vars.get(key).cloned()).unwrap();
+    assert_eq!(exported.libs, lib.libs);
+    assert_eq!(exported.frameworks, lib.frameworks);
+    assert_eq!(exported.defines, lib.defines);
+    assert_eq!(exported.ld_args, lib.ld_args);
+}
+
+#[test]
+fn inherit() {
+    let upstream = create_config("toml-good", vec![]).probe_full().unwrap();
+    let env = dep_env_vars("TEST", &upstream);
+
+    let (libraries, flags) = toml("toml-inherit", env).unwrap();
+    let testdata = libraries.get_by_name("testdata").unwrap();
+    assert_eq!(testdata.source, Source::Inherited);
+    assert_eq!(testdata.version, "4.5.6");
+    let testlib = libraries.get_by_name("testlib-inherited").unwrap();
+    assert_eq!(testlib.source, Source::Inherited);
+    assert_eq!(testlib.name, "testlib");
+    assert_eq!(testlib.version, "1.2.3");
+
+    // Link flags have already been emitted by the upstream crate
+    let flags = flags.to_string();
+    assert!(!flags.contains("rustc-link"));
+    assert!(flags.contains("cargo:include=/usr/include/testlib\n"));
+
+    let err = toml("toml-inherit", vec![]).unwrap_err();
+    assert_matches!(err, Error::DepEnv(_));
+
+    // Optional dependencies are skipped if the upstream crate didn't find them,
+    // or found an unsuitable version
+    let (libraries, _) = toml("toml-inherit-optional", dep_env_vars("TEST", &upstream)).unwrap();
+    assert!(libraries.iter().is_empty());
+}
+
+#[test]
+fn rust_module() {
+    let (libraries, _) = toml("toml-good", vec![]).unwrap();
+    let path = env::temp_dir().join("system-deps-rust-module.rs");
+    libraries.write_rust_module(&path).unwrap();
+    let module = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        module,
+        r#"// Generated by system-deps
+
+#[allow(dead_code)]
+pub mod testdata {
+    pub const NAME: &str = "testdata";
+    pub const VERSION: &str = "4.5.6";
+    pub const MAJOR: u32 = 4;
+    pub const MINOR: u32 = 5;
+    pub const MICRO: u32 = 6;
+    pub const STATIC: bool = false;
+
+    #[allow(non_upper_case_globals)]
+    pub mod defines {}
+}
+
+#[allow(dead_code)]
+pub mod testlib {
+    pub const NAME: &str = "testlib";
+    pub const VERSION: &str = "1.2.3";
+    pub const MAJOR: u32 = 1;
+    pub const MINOR: u32 = 2;
+    pub const MICRO: u32 = 3;
+    pub const STATIC: bool = false;
+
+    #[allow(non_upper_case_globals)]
+    pub mod defines {
+        pub const AWESOME: Option<&str> = None;
+        pub const BADGER: Option<&str> = Some("yes");
+    }
+}
+"#
+    );
+
+    // The name actually used is the one of the alternative found
+    let (libraries, _) = toml("toml-alternatives", vec![]).unwrap();
+    let module = libraries.rust_module().unwrap();
+    assert!(module.contains("pub mod testlib {\n    pub const NAME: &str = \"testlib-3.0\";\n"));
+    assert!(module.contains("    pub const MAJOR: u32 = 3;\n    pub const MINOR: u32 = 0;\n"));
+
+    // Defines which are not valid identifiers are mangled
+    let (mut libraries, _) = toml("toml-good", vec![]).unwrap();
+    let testlib = libraries.libs.get_mut("testlib").unwrap();
+    testlib.defines.insert("2D".into(), None);
+    testlib.defines.insert("HAVE-X".into(), Some("1".into()));
+    let module = libraries.rust_module().unwrap();
+    assert!(module.contains("        pub const _2D: Option<&str> = None;\n"));
+    assert!(module.contains("        pub const HAVE_X: Option<&str> = Some(\"1\");\n"));
+
+    // but they must not collide
+    let testlib = libraries.libs.get_mut("testlib").unwrap();
+    testlib.defines.insert("HAVE_X".into(), None);
+    assert_eq!(
+        libraries.rust_module().unwrap_err(),
+        "testlib: defines HAVE-X and HAVE_X are both written as the Rust identifier HAVE_X"
+    );
+    let path = env::temp_dir().join("system-deps-rust-module-invalid.rs");
+    let err = libraries.write_rust_module(&path).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(!path.exists());
+}
+
+#[test]
+fn rerun_if_changed() {
+    let (_, flags) = toml(
+        "toml-good",
+        vec![
+            ("PKG_CONFIG_PATH", "src/tests"),
+            ("SYSTEM_DEPS_PKG_CONFIG_BACKEND", "builtin"),
+        ],
+    )
+    .unwrap();
+    let flags = flags.to_string();
+    assert!(flags.contains("cargo:rerun-if-changed=src/tests/testlib.pc\n"));
+    assert!(flags.contains("cargo:rerun-if-changed=src/tests/testdata.pc\n"));
+    assert!(flags.contains("cargo:rerun-if-env-changed=TESTLIB_NO_PKG_CONFIG\n"));
+
+    // The binary locates the files itself
+    let (_, flags) = toml(
+        "toml-good",
+        vec![
+            ("PKG_CONFIG_PATH", "src/tests"),
+            ("SYSTEM_DEPS_PKG_CONFIG_BACKEND", "binary"),
+        ],
+    )
+    .unwrap();
+    let flags = flags.to_string();
+    assert!(flags.contains("cargo:rerun-if-changed=src/tests/testlib.pc\n"));
+    assert!(flags.contains("cargo:rerun-if-changed=src/tests/testdata.pc\n"));
+
+    // Requirements of the package are tracked as well
+    let config = create_config("toml-good", vec![("PKG_CONFIG_PATH", "src/tests")]);
+    let expected = vec![
+        PathBuf::from("src/tests/testrequires.pc"),
+        PathBuf::from("src/tests/testlib.pc"),
+        PathBuf::from("src/tests/testdata.pc"),
+        PathBuf::from("src/tests/testanotherlib.pc"),
+    ];
+    let files = pc::Resolver::new(&config.env).files("testrequires");
+    assert_eq!(files, expected);
+    let files = pc::Command::new(&config.env).files("testrequires");
+    assert_eq!(files, expected);
+
+    // The files located by the binary are used if they differ from the resolver's ones
+    let vcpkg_dir = Path::new("src/tests/vcpkg/x64-linux/lib/pkgconfig");
+    let files = pc::Command::new(&config.env)
+        .prepend_search_path(vcpkg_dir)
+        .files("testvcpkg");
+    assert_eq!(files, vec![vcpkg_dir.join("testvcpkg.pc")]);
+
+    // Variables read for the target
+    let (_, flags) = toml(
+        "toml-good",
+        vec![
+            ("TARGET", "aarch64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("PKG_CONFIG_ALLOW_CROSS", "1"),
+        ],
+    )
+    .unwrap();
+    let flags = flags.to_string();
+    for var in [
+        "PKG_CONFIG_PATH_aarch64-unknown-linux-gnu",
+        "PKG_CONFIG_PATH_aarch64_unknown_linux_gnu",
+        "TARGET_PKG_CONFIG_PATH",
+        "PKG_CONFIG_PATH",
+        "SYSTEM_DEPS_SYSROOT_AARCH64_UNKNOWN_LINUX_GNU",
+        "PKG_CONFIG_SYSROOT_DIR_aarch64_unknown_linux_gnu",
+    ] {
+        assert!(
+            flags.contains(&format!("cargo:rerun-if-env-changed={}\n", var)),
+            "{}",
+            var
+        );
+    }
+}
@@ -0,0 +1,5 @@
+#ifndef TESTCHECK_ENABLED
+#error "TESTCHECK_ENABLED is not defined"
+#endif
+
+int testcheck_init(void);
@@ -0,0 +1,3 @@
+#define TESTHEADER_MAJOR 2
+#define TESTHEADER_MINOR 4
+#define TESTHEADER_MICRO 1
@@ -0,0 +1,7 @@
+#ifndef TESTSEARCH_H
+#define TESTSEARCH_H
+
+#define TESTSEARCH_MAJOR 1
+#define TESTSEARCH_MINOR 3
+
+#endif
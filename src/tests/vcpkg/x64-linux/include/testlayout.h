@@ -0,0 +1,2 @@
+#define TESTLAYOUT_MAJOR 3
+#define TESTLAYOUT_MINOR 1
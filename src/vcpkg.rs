@@ -0,0 +1,150 @@
+// Find libraries installed by vcpkg, in the tree of the triplet matching the target
+
+use std::path::{Path, PathBuf};
+
+use crate::{EnvVariables, EnvVariablesExt};
+
+#[derive(Debug)]
+pub(crate) struct Vcpkg {
+    tree: PathBuf,
+    triplet: String,
+    debug: bool,
+}
+
+impl Vcpkg {
+    /// The vcpkg installed tree defined in the environment, if any.
+    pub(crate) fn from_env(env: &EnvVariables) -> Option<Self> {
+        let installed = match env.get("VCPKG_INSTALLED_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let root = PathBuf::from(env.get("VCPKG_ROOT")?);
+                // Manifest mode installs the packages next to `vcpkg.json`
+                let manifest = env
+                    .get("CARGO_MANIFEST_DIR")
+                    .map(|d| Path::new(&d).join("vcpkg_installed"))
+                    .filter(|d| d.is_dir());
+                manifest.unwrap_or_else(|| root.join("installed"))
+            }
+        };
+
+        let triplet = env.get("VCPKG_DEFAULT_TRIPLET").or_else(|| {
+            let target = env.get("TARGET")?;
+            let static_crt = env
+                .get("CARGO_CFG_TARGET_FEATURE")
+                .is_some_and(|f| f.split(',').any(|f| f == "crt-static"));
+            triplet(&target, static_crt)
+        })?;
+
+        Some(Self {
+            tree: installed.join(&triplet),
+            debug: env.get("PROFILE").as_deref() == Some("debug"),
+            triplet,
+        })
+    }
+
+    /// Whether the triplet only provides static libraries.
+    pub(crate) fn is_static(&self) -> bool {
+        let t = &self.triplet;
+        if t.ends_with("-static") || t.contains("-static-") {
+            true
+        } else if t.ends_with("-dynamic") {
+            false
+        } else {
+            // Triplets of other platforms than Windows are static by default
+            !t.contains("windows") && !t.contains("uwp")
+        }
+    }
+
+    /// Directories containing the `.pc` files, using the debug ones for debug builds.
+    pub(crate) fn pkg_config_dirs(&self) -> Vec<PathBuf> {
+        vec![
+            self.lib_dir().join("pkgconfig"),
+            self.tree.join("share").join("pkgconfig"),
+        ]
+    }
+
+    pub(crate) fn lib_dir(&self) -> PathBuf {
+        if self.debug {
+            self.tree.join("debug").join("lib")
+        } else {
+            self.tree.join("lib")
+        }
+    }
+
+    pub(crate) fn include_dir(&self) -> PathBuf {
+        self.tree.join("include")
+    }
+}
+
+// The default vcpkg triplet for the Rust target
+fn triplet(target: &str, static_crt: bool) -> Option<String> {
+    let mut parts = target.split('-');
+    let arch = match parts.next()? {
+        "x86_64" => "x64",
+        "i686" | "i586" => "x86",
+        "aarch64" => "arm64",
+        a if a.starts_with("arm") => "arm",
+        _ => return None,
+    };
+
+    let triplet = if target.contains("windows-msvc") {
+        if static_crt {
+            format!("{arch}-windows-static")
+        } else {
+            format!("{arch}-windows")
+        }
+    } else if target.contains("windows-gnu") {
+        format!("{arch}-mingw-static")
+    } else if target.contains("apple-darwin") {
+        format!("{arch}-osx")
+    } else if target.contains("linux") {
+        format!("{arch}-linux")
+    } else {
+        return None;
+    };
+
+    Some(triplet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_triplet() {
+        let t = triplet;
+
+        assert_eq!(t("x86_64-unknown-linux-gnu", false).unwrap(), "x64-linux");
+        assert_eq!(
+            t("aarch64-unknown-linux-gnu", false).unwrap(),
+            "arm64-linux"
+        );
+        assert_eq!(t("x86_64-pc-windows-msvc", false).unwrap(), "x64-windows");
+        assert_eq!(
+            t("x86_64-pc-windows-msvc", true).unwrap(),
+            "x64-windows-static"
+        );
+        assert_eq!(t("i686-pc-windows-gnu", false).unwrap(), "x86-mingw-static");
+        assert_eq!(t("aarch64-apple-darwin", false).unwrap(), "arm64-osx");
+        assert_eq!(t("wasm32-unknown-unknown", false), None);
+    }
+
+    #[test]
+    fn static_triplet() {
+        let is_static = |triplet: &str| {
+            Vcpkg {
+                tree: PathBuf::new(),
+                triplet: triplet.to_string(),
+                debug: false,
+            }
+            .is_static()
+        };
+
+        assert!(is_static("x64-linux"));
+        assert!(is_static("x64-windows-static"));
+        assert!(is_static("x64-windows-static-md"));
+        assert!(!is_static("x64-windows"));
+        assert!(!is_static("x64-linux-dynamic"));
+        assert!(!is_static("arm64-uwp"));
+    }
+}
@@ -0,0 +1,281 @@
+// Parse and evaluate pkg-config style version requirements
+
+use std::{fmt, ops::Bound};
+
+use version_compare::Cmp;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ParseError {
+    Empty,
+    MissingVersion(String),
+    InvalidVersion(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty version requirement"),
+            Self::MissingVersion(c) => write!(f, "missing version after `{c}`"),
+            Self::InvalidVersion(v) => write!(f, "invalid version `{v}`"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Comparator {
+    op: Cmp,
+    version: String,
+}
+
+impl Comparator {
+    fn parse(s: &str) -> Result<Self, ParseError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        // Longest operators first so `>=` isn't parsed as `>` followed by `=1.0`
+        let (op, rest) = [
+            (">=", Cmp::Ge),
+            ("<=", Cmp::Le),
+            ("!=", Cmp::Ne),
+            ("==", Cmp::Eq),
+            ("=", Cmp::Eq),
+            (">", Cmp::Gt),
+            ("<", Cmp::Lt),
+        ]
+        .iter()
+        .find_map(|(sign, op)| s.strip_prefix(sign).map(|rest| (*op, rest)))
+        // A bare version means "at least this version"
+        .unwrap_or((Cmp::Ge, s));
+
+        let version = rest.trim();
+        if version.is_empty() {
+            return Err(ParseError::MissingVersion(s.to_string()));
+        }
+        if version.contains(char::is_whitespace)
+            || version.starts_with(['<', '>', '=', '!'])
+            || version_compare::Version::from(version).is_none()
+        {
+            return Err(ParseError::InvalidVersion(version.to_string()));
+        }
+
+        Ok(Self {
+            op,
+            version: version.to_string(),
+        })
+    }
+
+    fn matches(&self, version: &str) -> bool {
+        version_compare::compare_to(version, &self.version, self.op).unwrap_or(false)
+    }
+}
+
+impl fmt::Display for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.op.sign(), self.version)
+    }
+}
+
+/// A version requirement such as `>= 2.60, != 2.62.1`.
+///
+/// The requirement is the conjunction of all its comma separated comparators.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+impl VersionReq {
+    pub(crate) fn parse(s: &str) -> Result<Self, ParseError> {
+        let comparators = s
+            .split(',')
+            .map(Comparator::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { comparators })
+    }
+
+    /// Check if `version` satisfies all the comparators.
+    pub(crate) fn matches(&self, version: &str) -> bool {
+        self.comparators.iter().all(|c| c.matches(version))
+    }
+
+    /// The tightest range bounding this requirement, suitable for `pkg_config::Config::range_version`.
+    ///
+    /// `!=` comparators cannot be expressed as a range so versions returned by a probe
+    /// using this range still have to be checked using [`VersionReq::matches`].
+    pub(crate) fn range(&self) -> VersionRange<'_> {
+        let mut start = Bound::Unbounded;
+        let mut end = Bound::Unbounded;
+
+        for c in self.comparators.iter() {
+            let v = c.version.as_str();
+            match c.op {
+                Cmp::Ge => start = tightest(start, Bound::Included(v), Cmp::Gt),
+                Cmp::Gt => start = tightest(start, Bound::Excluded(v), Cmp::Gt),
+                Cmp::Le => end = tightest(end, Bound::Included(v), Cmp::Lt),
+                Cmp::Lt => end = tightest(end, Bound::Excluded(v), Cmp::Lt),
+                Cmp::Eq => {
+                    start = tightest(start, Bound::Included(v), Cmp::Gt);
+                    end = tightest(end, Bound::Included(v), Cmp::Lt);
+                }
+                _ => {}
+            }
+        }
+
+        VersionRange { start, end }
+    }
+
+    /// The lowest version accepted by this requirement, if bounded.
+    pub(crate) fn min_version(&self) -> Option<&str> {
+        match self.range().start {
+            Bound::Included(v) | Bound::Excluded(v) => Some(v),
+            Bound::Unbounded => None,
+        }
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, c) in self.comparators.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{c}")?;
+        }
+        Ok(())
+    }
+}
+
+// Return the most restrictive of the two bounds, `wanted` being the ordering of
+// the versions which makes a bound more restrictive.
+fn tightest<'a>(current: Bound<&'a str>, new: Bound<&'a str>, wanted: Cmp) -> Bound<&'a str> {
+    let (cur_v, new_v) = match (&current, &new) {
+        (Bound::Unbounded, _) => return new,
+        (_, Bound::Unbounded) => return current,
+        (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) => {
+            (*a, *b)
+        }
+    };
+
+    match version_compare::compare(new_v, cur_v) {
+        Ok(Cmp::Eq) => match new {
+            // Excluding a version is more restrictive than including it
+            Bound::Excluded(_) => new,
+            _ => current,
+        },
+        Ok(c) if c == wanted => new,
+        _ => current,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct VersionRange<'a> {
+    start: Bound<&'a str>,
+    end: Bound<&'a str>,
+}
+
+impl<'a> std::ops::RangeBounds<&'a str> for VersionRange<'a> {
+    fn start_bound(&self) -> Bound<&&'a str> {
+        self.start.as_ref()
+    }
+
+    fn end_bound(&self) -> Bound<&&'a str> {
+        self.end.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::RangeBounds;
+
+    #[test]
+    fn parse() {
+        for s in [
+            "1.2",
+            ">= 1.2",
+            ">=1.2",
+            ">= 1.2, < 2.0",
+            "= 1.2.3",
+            "== 1.2.3",
+            "!= 1.2.3",
+            "<= 2",
+            "> 1",
+            ">= 2.60, != 2.62.1",
+        ] {
+            assert!(VersionReq::parse(s).is_ok(), "{}", s);
+        }
+
+        assert_eq!(VersionReq::parse(""), Err(ParseError::Empty));
+        assert_eq!(VersionReq::parse(">= 1,"), Err(ParseError::Empty));
+        assert_eq!(
+            VersionReq::parse(">="),
+            Err(ParseError::MissingVersion(">=".into()))
+        );
+        assert_eq!(
+            VersionReq::parse(">= 1 2"),
+            Err(ParseError::InvalidVersion("1 2".into()))
+        );
+        assert_eq!(
+            VersionReq::parse("=> 1"),
+            Err(ParseError::InvalidVersion("> 1".into()))
+        );
+    }
+
+    #[test]
+    fn matches() {
+        let req = VersionReq::parse(">= 2.60, != 2.62.1").unwrap();
+        assert!(req.matches("2.60"));
+        assert!(req.matches("2.62.0"));
+        assert!(!req.matches("2.62.1"));
+        assert!(req.matches("2.62.2"));
+        assert!(!req.matches("2.58"));
+
+        let req = VersionReq::parse("= 1.2.3").unwrap();
+        assert!(req.matches("1.2.3"));
+        assert!(!req.matches("1.2.4"));
+
+        let req = VersionReq::parse("> 1, <= 2").unwrap();
+        assert!(!req.matches("1"));
+        assert!(req.matches("1.0.1"));
+        assert!(req.matches("2"));
+        assert!(!req.matches("2.0.1"));
+
+        let req = VersionReq::parse("1.2").unwrap();
+        assert!(req.matches("1.2"));
+        assert!(req.matches("3"));
+        assert!(!req.matches("1.1"));
+    }
+
+    #[test]
+    fn range() {
+        let req = VersionReq::parse(">= 1, > 1.5, < 3, <= 2").unwrap();
+        let range = req.range();
+        assert_eq!(range.start_bound(), Bound::Excluded(&"1.5"));
+        assert_eq!(range.end_bound(), Bound::Included(&"2"));
+        assert_eq!(req.min_version(), Some("1.5"));
+
+        let req = VersionReq::parse("= 1.2.3").unwrap();
+        let range = req.range();
+        assert_eq!(range.start_bound(), Bound::Included(&"1.2.3"));
+        assert_eq!(range.end_bound(), Bound::Included(&"1.2.3"));
+
+        let req = VersionReq::parse("!= 1.2.3").unwrap();
+        let range = req.range();
+        assert_eq!(range.start_bound(), Bound::Unbounded);
+        assert_eq!(range.end_bound(), Bound::Unbounded);
+        assert_eq!(req.min_version(), None);
+
+        let req = VersionReq::parse(">= 1.2, > 1.2").unwrap();
+        assert_eq!(req.range().start_bound(), Bound::Excluded(&"1.2"));
+    }
+
+    #[test]
+    fn display() {
+        let req = VersionReq::parse("1.2,<2,!=1.5").unwrap();
+        assert_eq!(req.to_string(), ">= 1.2, < 2, != 1.5");
+    }
+}